@@ -0,0 +1,150 @@
+use std::io::Cursor;
+use std::time::Instant;
+
+use lzxd::{Lzxd, WindowSize};
+use oxidizer::reader::TypeReaderRegistry;
+use oxidizer::surface::AlphaMode;
+use oxidizer::xnb::{self, XNBError, XNBFile, XnbInput};
+
+// Decodes a synthetic size x size Color texture, uncompressed and LZX compressed, and prints
+// how much memory each took at its peak. Streaming should stay close to the size of the image,
+// copying the file and decompressing all of it first adds both on top.
+//
+// cargo bench --bench streaming -- 4096
+fn main() {
+    let size = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(4096);
+    if let Err(error) = run(size) {
+        eprintln!("Benchmark failed: {}", error);
+    }
+}
+
+fn run(size: u32) -> Result<(), XNBError> {
+    let plain = texture_xnb(size);
+    let lzx = lzx_copy(&plain);
+
+    let image_size = size as usize * size as usize * 4;
+    println!("{}x{} texture, {} MiB of pixels", size, size, image_size >> 20);
+    println!("{:<12} {:<10} {:>14} {:>10}", "file", "reading", "peak memory", "time");
+
+    // The files are already in memory and not counted, reading from disk streams the same way.
+    let readers = TypeReaderRegistry::builtin(AlphaMode::Premultiplied);
+    for (name, file) in [("plain", &plain), ("lzx", &lzx)] {
+        measure(name, "streamed", || xnb::read_xnb(XnbInput::new(&file[..], file.len()), &readers))?;
+        measure(name, "in memory", || {
+            let vec = if name == "lzx" { decompress(file) } else { file.clone() };
+            let length = vec.len();
+            xnb::read_xnb(XnbInput::new(Cursor::new(vec), length), &readers)
+        })?;
+    }
+    Ok(())
+}
+
+fn measure(name: &str, mode: &str, read: impl FnOnce() -> Result<XNBFile, XNBError>) -> Result<(), XNBError> {
+    reset_peak_memory();
+    let before = memory("VmRSS:");
+    let start = Instant::now();
+    let file = read()?;
+    let elapsed = start.elapsed();
+
+    let peak = match (before, memory("VmHWM:")) {
+        (Some(before), Some(peak)) => format!("{} MiB", peak.saturating_sub(before) >> 20),
+        _ => "n/a".to_owned(),
+    };
+    println!("{:<12} {:<10} {:>14} {:>8}ms", name, mode, peak, elapsed.as_millis());
+    drop(file);
+    Ok(())
+}
+
+// An uncompressed Windows XNA 4.0 file holding a single Color texture without mipmaps.
+fn texture_xnb(size: u32) -> Vec<u8> {
+    let reader = b"Microsoft.Xna.Framework.Content.Texture2DReader";
+    let data_size = size * size * 4;
+
+    let mut content = vec![1, reader.len() as u8];
+    content.extend_from_slice(reader);
+    // reader version, no shared resources, then the primary asset
+    content.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+    for value in [0, size, size, 1, data_size] {
+        content.extend_from_slice(&value.to_le_bytes());
+    }
+    content.extend((0..data_size).map(|i| (i * 7) as u8));
+
+    let mut file = vec![b'X', b'N', b'B', b'w', 5, 0];
+    file.extend_from_slice(&((content.len() + 10) as u32).to_le_bytes());
+    file.extend_from_slice(&content);
+    file
+}
+
+// Stores the content of an uncompressed XNB in uncompressed LZX blocks, one per frame.
+fn lzx_copy(plain: &[u8]) -> Vec<u8> {
+    let content = &plain[10..];
+
+    let mut compressed = Vec::new();
+    for (i, frame) in content.chunks(0x8000).enumerate() {
+        let mut chunk = Vec::new();
+        // E8 translation flag in the first frame, block type 3 and the 24 bit block size.
+        let (bits, count) = if i == 0 { (0b0011u64, 4) } else { (0b011, 3) };
+        let header = (bits << 24 | frame.len() as u64) << (32 - count - 24);
+        chunk.extend_from_slice(&((header >> 16) as u16).to_le_bytes());
+        chunk.extend_from_slice(&(header as u16).to_le_bytes());
+        // R0, R1 and R2
+        for _ in 0..3 {
+            chunk.extend_from_slice(&1u32.to_le_bytes());
+        }
+        chunk.extend_from_slice(frame);
+        if chunk.len() % 2 == 1 {
+            chunk.push(0);
+        }
+
+        if frame.len() != 0x8000 {
+            compressed.push(0xFF);
+            compressed.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+        }
+        compressed.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+        compressed.extend_from_slice(&chunk);
+    }
+
+    // Windows, XNA 4.0, HiDef and LZX
+    let mut file = vec![b'X', b'N', b'B', b'w', 5, 0x81];
+    file.extend_from_slice(&((compressed.len() + 14) as u32).to_le_bytes());
+    file.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    file.extend_from_slice(&compressed);
+    file
+}
+
+// Decompresses every frame up front and returns the file as if it was never compressed,
+// the way XNB files were read before streaming.
+fn decompress(lzx: &[u8]) -> Vec<u8> {
+    let mut input = &lzx[14..];
+    let mut decompressor = Lzxd::new(WindowSize::KB64);
+    let mut content = Vec::new();
+    while input.len() >= 2 {
+        let mut header = 2;
+        let mut block_size = u16::from_be_bytes([input[0], input[1]]) as usize;
+        if input[0] == 0xFF {
+            header = 5;
+            block_size = u16::from_be_bytes([input[3], input[4]]) as usize;
+        }
+
+        let chunk = &input[header..header + block_size];
+        content.extend_from_slice(decompressor.decompress_next(chunk).expect("valid LZX"));
+        input = &input[header + block_size..];
+    }
+
+    let mut file = vec![b'X', b'N', b'B', b'w', 5, 0];
+    file.extend_from_slice(&((content.len() + 10) as u32).to_le_bytes());
+    file.extend_from_slice(&content);
+    file
+}
+
+// Resident memory in bytes, Linux only.
+fn memory(field: &str) -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(field))?;
+    let kb: usize = line[field.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn reset_peak_memory() {
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
@@ -1,29 +1,49 @@
-use std::collections::{HashMap, HashSet};
-use std::ffi::OsString;
-use std::fmt::Debug;
-use std::fs::DirEntry;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use image::{ImageBuffer, Rgba};
-use image::DynamicImage::ImageLumaA8;
-use indicatif::{ParallelProgressIterator, ProgressBar};
-use rayon::iter::IntoParallelRefIterator;
 
-use crate::{ registry, util, xnb};
-use crate::util::TerrariaFile;
-use crate::xnb::XNBFile;
+use crate::effect::Effect;
+use crate::font::Font;
+use crate::sound::Sound;
+use crate::reader::TypeReaderRegistry;
+use crate::xnb;
+use crate::xnb::{XNBError, XNBFile};
 use crate::xnb::XNBFile::Texture;
 
 pub type Sprite = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
 pub struct ResourceManager {
-    pub sprite_path: PathBuf
+    pub sprite_path: PathBuf,
+    pub sound_path: PathBuf,
+    pub font_path: PathBuf,
+    pub effect_path: PathBuf,
+    pub readers: TypeReaderRegistry,
 }
 
+#[derive(Clone, Copy)]
 pub enum ResourcePath {
     Tile(u32),
     Wall(u32),
     Item(u32),
+    Npc(u32),
+    Projectile(u32),
+    Background(u32),
+    ArmorHead(u32),
+    ArmorBody(u32),
+    ArmorLegs(u32),
+    Wings(u32),
+    /// Acc_{slot}_{id}, like Acc_Shoes_1.
+    Accessory(&'static str, u32),
+    /// Player_{skin variant}_{part}.
+    Player(u32, u32),
+    Buff(u32),
+    Liquid(u32),
+    LiquidSlope(u32),
+    Waterfall(u32),
+    /// Tiles_5_{n}, the trunks of biome trees.
+    TreeTrunk(u32),
+    TreeTops(u32),
+    TreeBranches(u32),
 }
 
 impl ResourcePath {
@@ -32,16 +52,56 @@ impl ResourcePath {
             ResourcePath::Tile(id) => format!("Tiles_{}", id),
             ResourcePath::Wall(id) => format!("Wall_{}", id),
             ResourcePath::Item(id) => format!("Item_{}", id),
+            ResourcePath::Npc(id) => format!("NPC_{}", id),
+            ResourcePath::Projectile(id) => format!("Projectile_{}", id),
+            ResourcePath::Background(id) => format!("Background_{}", id),
+            ResourcePath::ArmorHead(id) => format!("Armor_Head_{}", id),
+            ResourcePath::ArmorBody(id) => format!("Armor_Body_{}", id),
+            ResourcePath::ArmorLegs(id) => format!("Armor_Legs_{}", id),
+            ResourcePath::Wings(id) => format!("Wings_{}", id),
+            ResourcePath::Accessory(slot, id) => format!("Acc_{}_{}", slot, id),
+            ResourcePath::Player(variant, part) => format!("Player_{}_{}", variant, part),
+            ResourcePath::Buff(id) => format!("Buff_{}", id),
+            ResourcePath::Liquid(id) => format!("Liquid_{}", id),
+            ResourcePath::LiquidSlope(id) => format!("LiquidSlope_{}", id),
+            ResourcePath::Waterfall(id) => format!("Waterfall_{}", id),
+            ResourcePath::TreeTrunk(id) => format!("Tiles_5_{}", id),
+            ResourcePath::TreeTops(id) => format!("Tree_Tops_{}", id),
+            ResourcePath::TreeBranches(id) => format!("Tree_Branches_{}", id),
         }
     }
 }
 
 impl ResourceManager {
-    pub fn get_sprite(&self, path: ResourcePath) -> Option<Sprite> {
-        let xnb = xnb::convert_xnb_file(self.sprite_path.join(path.get_string() + ".xnb")).unwrap();
-        match xnb {
-            Texture(sprite) => Some(sprite),
-            _ => None
+    pub fn get_sprite(&self, path: ResourcePath) -> Result<Sprite, XNBError> {
+        self.get_mipmaps(path)?.into_iter().next().ok_or(XNBError::UnexpectedObject("Texture2D"))
+    }
+
+    pub fn get_mipmaps(&self, path: ResourcePath) -> Result<Vec<Sprite>, XNBError> {
+        match xnb::convert_xnb_file(self.sprite_path.join(path.get_string() + ".xnb"), &self.readers)? {
+            Texture(levels) => Ok(levels),
+            _ => Err(XNBError::UnexpectedObject("Texture2D"))
+        }
+    }
+
+    pub fn get_sound(&self, name: &str) -> Result<Sound, XNBError> {
+        match xnb::convert_xnb_file(self.sound_path.join(name.to_owned() + ".xnb"), &self.readers)? {
+            XNBFile::Sound(sound) => Ok(sound),
+            _ => Err(XNBError::UnexpectedObject("SoundEffect"))
+        }
+    }
+
+    pub fn get_font(&self, name: &str) -> Result<Font, XNBError> {
+        match xnb::convert_xnb_file(self.font_path.join(name.to_owned() + ".xnb"), &self.readers)? {
+            XNBFile::Font(font) => Ok(font),
+            _ => Err(XNBError::UnexpectedObject("SpriteFont"))
+        }
+    }
+
+    pub fn get_effect(&self, name: &str) -> Result<Effect, XNBError> {
+        match xnb::convert_xnb_file(self.effect_path.join(name.to_owned() + ".xnb"), &self.readers)? {
+            XNBFile::Effect(effect) => Ok(effect),
+            _ => Err(XNBError::UnexpectedObject("Effect"))
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,57 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Needed {needed} more bytes at offset {offset}, but the data ended.")]
+pub struct Truncated {
+    pub offset: usize,
+    pub needed: usize,
+}
+
+/// Bounds checked reading of fixed layout structures, like effect bytecode or XACT banks, from a byte slice.
+#[derive(Copy, Clone)]
+pub struct SliceReader<'a> {
+    pub data: &'a [u8],
+    pub offset: usize,
+    pub big_endian: bool,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<(), Truncated> {
+        self.bytes(count).map(|_| ())
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Result<&'a [u8], Truncated> {
+        let bytes = self.offset.checked_add(count)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or(Truncated { offset: self.offset, needed: count })?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Truncated> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Truncated> {
+        let bytes = self.bytes(2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Truncated> {
+        let bytes = self.bytes(4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// Reads `size` bytes as a string that ends at the first null byte.
+    pub fn string(&mut self, size: usize) -> Result<String, Truncated> {
+        let bytes = self.bytes(size)?;
+        let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Serialize;
+
+use crate::binary::SliceReader;
+use crate::xnb::XNBError;
+
+// XNA 4.0 puts its own header in front of the D3DX effect.
+const XNA_EFFECT_MAGIC: u32 = 0xBCF0_0BCF;
+const FX_2_0_TAG: u32 = 0xFEFF_0901;
+// Structs nest, keeps corrupt bytecode from overflowing the stack.
+const MAX_DEPTH: usize = 16;
+
+/// A compiled Effect. Only the D3DX fx_2_0 blob is kept, which is what fxc writes to .fxo files.
+#[derive(Clone)]
+pub struct Effect {
+    pub bytecode: Vec<u8>,
+}
+
+/// The techniques and parameters declared by an effect.
+#[derive(Serialize)]
+pub struct EffectDescription {
+    pub techniques: Vec<Technique>,
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Serialize)]
+pub struct Technique {
+    pub name: String,
+    pub passes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub semantic: String,
+    /// scalar, vector, matrix_rows, matrix_columns, object or struct.
+    pub class: &'static str,
+    /// The HLSL type, float, sampler2D, texture, ...
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    pub rows: u32,
+    pub columns: u32,
+    /// Array length, 0 when the parameter is not an array.
+    pub elements: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<Parameter>,
+}
+
+impl Effect {
+    /// Strips the XNA 4.0 header, the bytecode of XNA 3.1 effects has none.
+    pub fn new(mut bytecode: Vec<u8>) -> Result<Effect, XNBError> {
+        let mut reader = SliceReader { data: &bytecode, offset: 0, big_endian: false };
+        if reader.u32()? == XNA_EFFECT_MAGIC {
+            let start = reader.u32()? as usize;
+            if start > bytecode.len() {
+                return Err(XNBError::InvalidEffect { offset: 4 });
+            }
+            bytecode.drain(..start);
+        }
+        Ok(Effect { bytecode })
+    }
+
+    pub fn describe(&self) -> Result<EffectDescription, XNBError> {
+        let mut reader = SliceReader { data: &self.bytecode, offset: 0, big_endian: false };
+        if reader.u32()? != FX_2_0_TAG {
+            return Err(XNBError::InvalidEffect { offset: 0 });
+        }
+        let header = reader.u32()? as usize;
+
+        // Every offset is relative to the end of the tag and header offset.
+        let base = SliceReader { data: self.bytecode.get(8..).unwrap_or_default(), offset: 0, big_endian: false };
+        let mut reader = SliceReader { offset: header, ..base };
+        let parameter_count = reader.u32()?;
+        let technique_count = reader.u32()?;
+        reader.u32()?;
+        let _object_count = reader.u32()?;
+
+        let mut parameters = Vec::new();
+        for _ in 0..parameter_count {
+            let type_offset = reader.u32()? as usize;
+            let _value_offset = reader.u32()?;
+            let _flags = reader.u32()?;
+            let annotation_count = reader.u32()? as usize;
+            reader.skip(annotation_count * 8)?;
+
+            parameters.push(read_parameter(&mut SliceReader { offset: type_offset, ..base }, 0)?);
+        }
+
+        let mut techniques = Vec::new();
+        for _ in 0..technique_count {
+            let name = read_string(base, reader.u32()? as usize)?;
+            let annotation_count = reader.u32()? as usize;
+            let pass_count = reader.u32()?;
+            reader.skip(annotation_count * 8)?;
+
+            let mut passes = Vec::new();
+            for _ in 0..pass_count {
+                passes.push(read_string(base, reader.u32()? as usize)?);
+                let annotation_count = reader.u32()? as usize;
+                let state_count = reader.u32()? as usize;
+                // Annotations are a type and value offset, states an operation, index, type and value.
+                reader.skip(annotation_count * 8 + state_count * 16)?;
+            }
+            techniques.push(Technique { name, passes });
+        }
+
+        Ok(EffectDescription { techniques, parameters })
+    }
+}
+
+// Length prefixed and null terminated, offset 0 is an empty string.
+fn read_string(base: SliceReader, offset: usize) -> Result<String, XNBError> {
+    if offset == 0 {
+        return Ok(String::new());
+    }
+    let mut reader = SliceReader { offset, ..base };
+    let length = reader.u32()? as usize;
+    Ok(reader.string(length)?)
+}
+
+// D3DX type definition, struct members follow inline.
+fn read_parameter(reader: &mut SliceReader, depth: usize) -> Result<Parameter, XNBError> {
+    if depth > MAX_DEPTH {
+        return Err(XNBError::InvalidEffect { offset: reader.offset });
+    }
+
+    let type_id = reader.u32()?;
+    let class_id = reader.u32()?;
+    let name_offset = reader.u32()? as usize;
+    let semantic_offset = reader.u32()? as usize;
+    let (name, semantic) = (read_string(*reader, name_offset)?, read_string(*reader, semantic_offset)?);
+    let elements = reader.u32()?;
+
+    let (mut rows, mut columns, mut members) = (0, 0, Vec::new());
+    match class_id {
+        0..=3 => (rows, columns) = (reader.u32()?, reader.u32()?),
+        5 => {
+            let member_count = reader.u32()?;
+            for _ in 0..member_count {
+                members.push(read_parameter(reader, depth + 1)?);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(Parameter {
+        name,
+        semantic,
+        class: match class_id {
+            0 => "scalar",
+            1 => "vector",
+            2 => "matrix_rows",
+            3 => "matrix_columns",
+            4 => "object",
+            5 => "struct",
+            _ => "unknown",
+        },
+        type_name: match type_id {
+            0 => "void",
+            1 => "bool",
+            2 => "int",
+            3 => "float",
+            4 => "string",
+            5 => "texture",
+            6 => "texture1D",
+            7 => "texture2D",
+            8 => "texture3D",
+            9 => "textureCUBE",
+            10 => "sampler",
+            11 => "sampler1D",
+            12 => "sampler2D",
+            13 => "sampler3D",
+            14 => "samplerCUBE",
+            15 => "pixelshader",
+            16 => "vertexshader",
+            17 => "pixelfragment",
+            18 => "vertexfragment",
+            _ => "unsupported",
+        },
+        rows,
+        columns,
+        elements,
+        members,
+    })
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::asset::Sprite;
+use crate::xnb::Rectangle;
+
+#[derive(Clone)]
+pub struct Font {
+    pub texture: Sprite,
+    /// Where each glyph sits in the texture.
+    pub glyphs: Vec<Rectangle>,
+    /// Offset and line box of each glyph when drawn.
+    pub cropping: Vec<Rectangle>,
+    pub characters: Vec<char>,
+    pub line_spacing: i32,
+    pub spacing: f32,
+    /// Left side bearing, glyph width and right side bearing of each glyph.
+    pub kerning: Vec<[f32; 3]>,
+    pub default_character: Option<char>,
+}
+
+#[derive(Serialize)]
+pub struct FontMetrics {
+    pub line_spacing: i32,
+    pub spacing: f32,
+    pub default_character: Option<char>,
+    pub glyphs: BTreeMap<char, GlyphMetrics>,
+}
+
+#[derive(Serialize)]
+pub struct GlyphMetrics {
+    pub bounds: Rectangle,
+    pub cropping: Rectangle,
+    pub left_bearing: f32,
+    pub width: f32,
+    pub right_bearing: f32,
+}
+
+impl Font {
+    pub fn metrics(&self) -> FontMetrics {
+        let glyphs = self.characters.iter()
+            .zip(&self.glyphs)
+            .zip(&self.cropping)
+            .zip(&self.kerning)
+            .map(|(((character, bounds), cropping), [left_bearing, width, right_bearing])| {
+                (*character, GlyphMetrics {
+                    bounds: *bounds,
+                    cropping: *cropping,
+                    left_bearing: *left_bearing,
+                    width: *width,
+                    right_bearing: *right_bearing,
+                })
+            })
+            .collect();
+
+        FontMetrics {
+            line_spacing: self.line_spacing,
+            spacing: self.spacing,
+            default_character: self.default_character,
+            glyphs,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Reading of XNA content (XNB) files, usable by other crates that need to register their own type readers.
+
+pub mod asset;
+pub mod binary;
+pub mod effect;
+pub mod font;
+pub mod reader;
+pub mod sound;
+pub mod surface;
+pub mod xnb;
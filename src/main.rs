@@ -1,28 +1,180 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
 use console::{Color, style};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
-use crate::asset::{ResourceManager, ResourcePath};
+use oxidizer::asset::{ResourceManager, ResourcePath, Sprite};
+use oxidizer::reader::TypeReaderRegistry;
+use oxidizer::surface::AlphaMode;
+use oxidizer::xnb::{self, FormatVersion, XNBError, XNBFile, XnbValue};
 
-mod asset;
 mod util;
-mod xnb;
+mod xact;
 mod mapper;
 mod registry;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (flags, args): (Vec<&str>, Vec<&str>) = args.iter().map(String::as_str).partition(|arg| arg.starts_with("--"));
+
+    // Keep XNA's premultiplied alpha instead of converting to the straight alpha PNGs normally use.
+    let alpha = if flags.contains(&"--premultiplied") {
+        AlphaMode::Premultiplied
+    } else {
+        AlphaMode::Straight
+    };
+
+    match args.first() {
+        Some(&"dump") => dump(&args[1..], alpha),
+        Some(&"pack") => pack(&args[1..], alpha),
+        Some(&"inspect") => inspect(&args[1..], flags.contains(&"--json")),
+        // Also write the authored mip chain next to each sprite as name.mip1.png, name.mip2.png, ...
+        // Animation frames go into a horizontal sheet unless they are asked for as separate files.
+        _ => export(alpha, flags.contains(&"--mipmaps"), flags.contains(&"--separate-frames")),
+    }
+}
+
+// Prints the decoded contents of XNB files as JSON.
+fn dump(files: &[&str], alpha: AlphaMode) {
+    let readers = TypeReaderRegistry::builtin(alpha);
+    for file in files {
+        let value = match xnb::convert_xnb_file(PathBuf::from(file), &readers) {
+            Ok(XNBFile::Texture(levels)) => XnbValue::Texture(levels),
+            Ok(XNBFile::Sound(sound)) => XnbValue::Sound(sound),
+            Ok(XNBFile::Font(font)) => XnbValue::Font(font),
+            Ok(XNBFile::Effect(effect)) => XnbValue::Effect(effect),
+            Ok(XNBFile::Value(value)) => value,
+            Ok(XNBFile::Unknown) => {
+                eprintln!("{}: no type reader for this content", file);
+                continue;
+            }
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                continue;
+            }
+        };
+
+        match serde_json::to_string_pretty(&value) {
+            Ok(json) => println!("{}", json),
+            Err(error) => eprintln!("{}: {}", file, error),
+        }
+    }
+}
+
+// Prints the header of XNB files, or of every XNB file in a directory, as a table or JSON.
+// oxidizer inspect [--json] <file-or-dir>...
+fn inspect(paths: &[&str], json: bool) {
+    let mut files = Vec::new();
+    for path in paths {
+        match util::find_xnb_files(Path::new(path)) {
+            Ok(found) => files.extend(found),
+            Err(error) => eprintln!("{}: {}", path, error),
+        }
+    }
+
+    if json {
+        let headers: Vec<_> = files.iter()
+            .map(|file| match xnb::inspect_xnb(file.clone()) {
+                Ok(header) => serde_json::json!({ "file": file, "header": header }),
+                Err(error) => serde_json::json!({ "file": file, "error": error.to_string() }),
+            })
+            .collect();
+
+        match serde_json::to_string_pretty(&headers) {
+            Ok(json) => println!("{}", json),
+            Err(error) => eprintln!("{}", error),
+        }
+        return;
+    }
+
+    println!("{:<40} {:<12} {:<7} {:<7} {:<11} {:>10} {:>12}  texture", "file", "platform", "version", "profile", "compression", "size", "decompressed");
+    for file in files {
+        let header = match xnb::inspect_xnb(file.clone()) {
+            Ok(header) => header,
+            Err(error) => {
+                println!("{:<40} {}", file.display(), error);
+                continue;
+            }
+        };
+
+        let texture = match &header.texture {
+            Some(texture) => format!("{} {}x{}, {} mips", texture.format, texture.width, texture.height, texture.mip_levels),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{:<40} {:<12} {:<7} {:<7} {:<11} {:>10} {:>12}  {}",
+            file.display(),
+            format!("{:?}", header.platform),
+            format!("{:?}", header.version),
+            match (header.version, header.hidef) {
+                (FormatVersion::Xna31, _) => "-",
+                (_, true) => "HiDef",
+                (_, false) => "Reach",
+            },
+            header.compression.map_or("none".to_owned(), |compression| format!("{:?}", compression)),
+            header.file_size,
+            header.decompressed_size,
+            texture,
+        );
+        for reader in &header.type_readers {
+            println!("    {} (version {})", reader.name, reader.version);
+        }
+    }
+}
+
+// Turns a Rustaria tile or wall sheet back into a Terraria Texture2D.
+// oxidizer pack <tile|wall> <input.png> <output.xnb>
+fn pack(args: &[&str], alpha: AlphaMode) {
+    let (kind, input, output) = match args {
+        [kind, input, output] => (*kind, *input, *output),
+        _ => {
+            eprintln!("Usage: oxidizer pack <tile|wall> <input.png> <output.xnb>");
+            return;
+        }
+    };
+
+    let sprite = match image::open(input) {
+        Ok(image) => image.into_rgba8(),
+        Err(error) => {
+            eprintln!("{}: {}", input, error);
+            return;
+        }
+    };
+
+    let sprite = match kind {
+        "tile" => mapper::unmap_tile(sprite),
+        "wall" => mapper::unmap_wall(sprite),
+        _ => {
+            eprintln!("Unknown sprite kind {}, expected tile or wall", kind);
+            return;
+        }
+    };
+
+    if let Err(error) = xnb::write_xnb_texture(PathBuf::from(output), &sprite, alpha) {
+        eprintln!("{}: {}", output, error);
+    }
+}
+
+fn export(alpha: AlphaMode, export_mipmaps: bool, separate_frames: bool) {
     let cwd = std::env::current_dir().expect("Could not access current working directory");
     if cwd.file_stem().unwrap() != "Content" {
         panic!("Not launched from Terraria's \"Content\" directory.")
     }
 
-    let mut progress_bar = new_progress_bar();
+    let progress_bar = new_progress_bar();
 
     let out = cwd.join("rustaria");
     let sprite = out.join("sprite");
 
     let manager = ResourceManager {
-        sprite_path: cwd.join("Images")
+        sprite_path: cwd.join("Images"),
+        sound_path: cwd.join("Sounds"),
+        font_path: cwd.join("Fonts"),
+        effect_path: cwd.clone(),
+        readers: TypeReaderRegistry::builtin(alpha),
     };
 
     let tile = sprite.join("tile");
@@ -31,9 +183,14 @@ fn main() {
 
 
     registry::BLOCK_TILES.par_iter().progress_with(progress_bar.clone()).for_each(|id| {
-        if let Some(sprite) = manager.get_sprite(ResourcePath::Tile(id.0)) {
-            let sprite = mapper::remap_tile(sprite);
-            let result = sprite.save(tile.join(format!("{}.png", id.1)));
+        let levels = match get_levels(&manager, ResourcePath::Tile(id.0), export_mipmaps) {
+            Ok(levels) => levels,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", id.1, error)),
+        };
+
+        for (level, sprite) in levels.into_iter().enumerate() {
+            let sprite = mapper::remap_tile(sprite, level as u32);
+            let result = sprite.save(tile.join(mip_file_name(id.1, level)));
 
             if let Err(error) = result {
                 progress_bar.println(format!("Failed to export {} {}", id.1, error));
@@ -46,9 +203,14 @@ fn main() {
 
 
     registry::WALLS.par_iter().progress_with(progress_bar.clone()).for_each(|id| {
-        if let Some(sprite) = manager.get_sprite(ResourcePath::Wall(id.0)) {
-            let sprite = mapper::remap_wall(sprite);
-            let result = sprite.save(wall.join(format!("{}.png", id.1)));
+        let levels = match get_levels(&manager, ResourcePath::Wall(id.0), export_mipmaps) {
+            Ok(levels) => levels,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", id.1, error)),
+        };
+
+        for (level, sprite) in levels.into_iter().enumerate() {
+            let sprite = mapper::remap_wall(sprite, level as u32);
+            let result = sprite.save(wall.join(mip_file_name(id.1, level)));
 
             if let Err(error) = result {
                 progress_bar.println(format!("Failed to export {} {}", id.1, error));
@@ -56,31 +218,647 @@ fn main() {
         }
     });
 
+    let sound = out.join("sound");
+    std::fs::create_dir_all(&sound).unwrap();
+    progress_bar.reset();
+
+    for file in util::seek_files(&manager.sound_path, &progress_bar, "Sounds") {
+        if !manager.sound_path.join(file.name.clone() + ".xnb").is_file() {
+            continue;
+        }
+
+        let audio = match manager.get_sound(&file.name) {
+            Ok(audio) => audio,
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", file.name, error));
+                continue;
+            }
+        };
+
+        let result = std::fs::File::create(sound.join(format!("{}.wav", file.name)))
+            .and_then(|mut wav| audio.write_wav(&mut std::io::BufWriter::new(&mut wav)));
+
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", file.name, error));
+        }
+    }
+
+    let font = out.join("font");
+    std::fs::create_dir_all(&font).unwrap();
+    progress_bar.reset();
 
-    //let test = sprite.join("test");
-    //std::fs::create_dir_all(&test).unwrap();
-    //for i in progress_bar.iter(1..5124, "Mapping Items") {
-    //    if let Some(sprite) =  manager.get_sprite(ResourcePath::Item(i)) {
-    //        let sprite = image::imageops::resize(&sprite, sprite.width() / 2, sprite.height() / 2, FilterType::Nearest);
-    //        sprite.save(test.join(format!("{}.png", i)));
-    //    }
-    //}
+    for file in util::seek_files(&manager.font_path, &progress_bar, "Fonts") {
+        if !manager.font_path.join(file.name.clone() + ".xnb").is_file() {
+            continue;
+        }
+
+        let sprite_font = match manager.get_font(&file.name) {
+            Ok(sprite_font) => sprite_font,
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", file.name, error));
+                continue;
+            }
+        };
+
+        let result = sprite_font.texture.save(font.join(format!("{}.png", file.name)));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", file.name, error));
+        }
+
+        let result = std::fs::File::create(font.join(format!("{}.json", file.name)))
+            .map_err(serde_json::Error::io)
+            .and_then(|json| serde_json::to_writer_pretty(json, &sprite_font.metrics()));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", file.name, error));
+        }
+    }
+
+    // Items drop the 2px gap under each frame, it is part of the frame for NPCs and projectiles.
+    let item = sprite.join("item");
+    export_animated(&manager, &registry::ITEMS, ResourcePath::Item, &registry::ITEM_FRAMES, 2, &item, separate_frames, &progress_bar);
+    report_unmapped_items(&manager.sprite_path, &item, &progress_bar);
+
+    let (npc, projectile) = (sprite.join("npc"), sprite.join("projectile"));
+    export_animated(&manager, &registry::NPCS, ResourcePath::Npc, &registry::NPC_FRAMES, 0, &npc, separate_frames, &progress_bar);
+    export_animated(&manager, &registry::PROJECTILES, ResourcePath::Projectile, &registry::PROJECTILE_FRAMES, 0, &projectile, separate_frames, &progress_bar);
+
+    export_equipment(&manager, &sprite, separate_frames, &progress_bar);
+    export_furniture(&manager, &sprite.join("furniture"), separate_frames, &progress_bar);
+    export_trees(&manager, &sprite.join("tree"), &progress_bar);
+    export_liquids(&manager, &sprite.join("liquid"), separate_frames, &progress_bar);
+    export_buffs(&manager, &sprite.join("buff"), &progress_bar);
+    export_backgrounds(&manager, &out.join("background"), &progress_bar);
+
+    // Shaders sit next to the asset directories, among other loose XNB files.
+    let effect = out.join("effect");
+    std::fs::create_dir_all(&effect).unwrap();
+    progress_bar.reset();
+
+    for file in util::seek_files(&manager.effect_path, &progress_bar, "Effects") {
+        let path = manager.effect_path.join(file.name.clone() + ".xnb");
+        let is_effect = path.is_file() && xnb::inspect_xnb(path).is_ok_and(|header| {
+            header.type_readers.first().is_some_and(|reader| reader.name.starts_with("Microsoft.Xna.Framework.Content.EffectReader"))
+        });
+        if !is_effect {
+            continue;
+        }
+
+        let shader = match manager.get_effect(&file.name) {
+            Ok(shader) => shader,
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", file.name, error));
+                continue;
+            }
+        };
+
+        let result = std::fs::write(effect.join(format!("{}.fxo", file.name)), &shader.bytecode);
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", file.name, error));
+        }
+
+        let description = match shader.describe() {
+            Ok(description) => description,
+            Err(error) => {
+                progress_bar.println(format!("Failed to describe {} {}", file.name, error));
+                continue;
+            }
+        };
+        let result = std::fs::File::create(effect.join(format!("{}.json", file.name)))
+            .map_err(serde_json::Error::io)
+            .and_then(|json| serde_json::to_writer_pretty(json, &description));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", file.name, error));
+        }
+    }
+
+    export_music(&cwd, &out.join("music"), &progress_bar);
 }
 
 
 
 
+// Writes every equip to equipment/<slot>/<name>.png and the base player to player/<variant>/<part>.png,
+// each with a descriptor of its animations.
+fn export_equipment(manager: &ResourceManager, sprite: &Path, separate_frames: bool, progress_bar: &ProgressBar) {
+    use registry::EquipSlot;
+
+    let mut sheets: Vec<(ResourcePath, PathBuf, String)> = Vec::new();
+    for &(slot, id, name) in registry::EQUIPMENT.iter() {
+        let (path, dir) = match slot {
+            EquipSlot::Head => (ResourcePath::ArmorHead(id), "head"),
+            EquipSlot::Body => (ResourcePath::ArmorBody(id), "body"),
+            EquipSlot::Legs => (ResourcePath::ArmorLegs(id), "legs"),
+            EquipSlot::Wings => (ResourcePath::Wings(id), "wings"),
+            EquipSlot::Accessory(slot) => (ResourcePath::Accessory(slot, id), "accessory"),
+        };
+        sheets.push((path, sprite.join("equipment").join(dir), name.to_owned()));
+    }
+
+    // Older versions have fewer skin variants.
+    for variant in 0..registry::PLAYER_VARIANTS {
+        for &(part, name) in registry::PLAYER_PARTS.iter() {
+            let path = ResourcePath::Player(variant, part);
+            if manager.sprite_path.join(path.get_string() + ".xnb").is_file() {
+                sheets.push((path, sprite.join("player").join(variant.to_string()), name.to_owned()));
+            }
+        }
+    }
+
+    progress_bar.reset();
+    progress_bar.set_length(sheets.len() as u64);
+    sheets.par_iter().progress_with(progress_bar.clone()).for_each(|(path, dir, name)| {
+        let sheet = match manager.get_sprite(*path) {
+            Ok(sheet) => sheet,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", name, error)),
+        };
+
+        let result = std::fs::create_dir_all(dir).map_err(image::ImageError::from).and_then(|_| {
+            if let ResourcePath::Wings(_) = path {
+                return export_frames(&mapper::slice_vertical(&sheet, registry::WING_FRAMES, 0), &[], dir, name, separate_frames);
+            }
+
+            match mapper::remap_player(&sheet) {
+                Some((frames, animations)) => export_frames(&frames, &animations, dir, name, separate_frames),
+                None => {
+                    progress_bar.println(format!("{} is not a 20 frame player sheet, it was copied as is", name));
+                    export_raw(&sheet, dir, name)
+                }
+            }
+        });
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", name, error));
+        }
+    });
+}
+
+// Writes every style of each piece of furniture to furniture/<name>/<style>.png, animated ones as frames,
+// with a furniture.json describing its size and origin in tiles and the frame count of each style.
+fn export_furniture(manager: &ResourceManager, furniture: &Path, separate_frames: bool, progress_bar: &ProgressBar) {
+    progress_bar.reset();
+    progress_bar.set_length(registry::FURNITURE.len() as u64);
+
+    registry::FURNITURE.par_iter().progress_with(progress_bar.clone()).for_each(|entry| {
+        let name = entry.name;
+        let sheet = match manager.get_sprite(ResourcePath::Tile(entry.id)) {
+            Ok(sheet) => sheet,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", name, error)),
+        };
+
+        let styles = mapper::slice_furniture(&sheet, entry);
+        if styles.is_empty() {
+            return progress_bar.println(format!("{} is smaller than a single {}x{} style", name, entry.width, entry.height()));
+        }
+
+        let dir = furniture.join(name);
+        let result = std::fs::create_dir_all(&dir).map_err(image::ImageError::from).and_then(|_| {
+            for (style, frames) in styles.iter().enumerate() {
+                if entry.frames > 1 {
+                    export_frames(frames, &[], &dir, &style.to_string(), separate_frames)?;
+                } else {
+                    frames[0].save(dir.join(format!("{}.png", style)))?;
+                }
+            }
+
+            let json = std::fs::File::create(dir.join("furniture.json"))?;
+            let descriptor = serde_json::json!({
+                "width": entry.width,
+                "height": entry.height(),
+                "origin": entry.origin,
+                "tile_width": entry.tile_width,
+                "tile_heights": entry.coordinate_heights,
+                "styles": styles.len(),
+                // Styles near the end of the sheet can be missing frames.
+                "frames": styles.iter().map(Vec::len).collect::<Vec<_>>(),
+            });
+            serde_json::to_writer_pretty(json, &descriptor).map_err(std::io::Error::from)?;
+            Ok(())
+        });
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", name, error));
+        }
+    });
+}
+
+// Writes the trunk, tops and branches of every tree style to tree/<name>/ with a tree.json descriptor.
+// Sizes and anchors are in Terraria pixels, 16 to a tile. An anchor is the pixel of a frame drawn
+// at the top left corner of the tile it belongs to.
+fn export_trees(manager: &ResourceManager, tree: &Path, progress_bar: &ProgressBar) {
+    use mapper::TreePart;
+
+    progress_bar.reset();
+    progress_bar.set_length(registry::TREES.len() as u64);
+
+    registry::TREES.par_iter().progress_with(progress_bar.clone()).for_each(|&(name, trunk, tops, branches)| {
+        let dir = tree.join(name);
+        let mut descriptor = serde_json::Map::new();
+        for part in mapper::TREE_PARTS {
+            let file = part.name();
+            let path = match part {
+                TreePart::Trunk => trunk.map_or(ResourcePath::Tile(5), ResourcePath::TreeTrunk),
+                TreePart::Tops => ResourcePath::TreeTops(tops),
+                TreePart::Branches => ResourcePath::TreeBranches(branches),
+            };
+            let sprite = match manager.get_sprite(path) {
+                Ok(sprite) => sprite,
+                Err(error) => {
+                    progress_bar.println(format!("Failed to read {} {} {}", name, file, error));
+                    continue;
+                }
+            };
+
+            let (sprite, entry) = match part {
+                TreePart::Trunk => match mapper::remap_tree(sprite) {
+                    Some(sprite) => (sprite, serde_json::json!({
+                        "frame_width": mapper::TREE_FRAME_SIZE,
+                        "frame_height": mapper::TREE_FRAME_SIZE,
+                        "frames": mapper::TREE_FRAMES.iter().map(|frame| frame.0).collect::<Vec<_>>(),
+                        "variants": mapper::TREE_VARIANTS,
+                        "anchor": mapper::TREE_TRUNK_ANCHOR,
+                    })),
+                    None => {
+                        progress_bar.println(format!("{} trunk is smaller than a tree sheet", name));
+                        continue;
+                    }
+                },
+                TreePart::Tops => {
+                    let stride = mapper::TREE_TOP_SIZE + mapper::TREE_PADDING;
+                    let entry = serde_json::json!({
+                        "frame_width": mapper::TREE_TOP_SIZE,
+                        "frame_height": mapper::TREE_TOP_SIZE,
+                        "stride": stride,
+                        "frames": (sprite.width() + mapper::TREE_PADDING) / stride,
+                        "anchor": mapper::TREE_TOP_ANCHOR,
+                    });
+                    (sprite, entry)
+                }
+                TreePart::Branches => {
+                    let stride = mapper::TREE_BRANCH_SIZE + mapper::TREE_PADDING;
+                    let entry = serde_json::json!({
+                        "frame_width": mapper::TREE_BRANCH_SIZE,
+                        "frame_height": mapper::TREE_BRANCH_SIZE,
+                        "stride": stride,
+                        "frames": (sprite.height() + mapper::TREE_PADDING) / stride,
+                        "left": { "column": 0, "anchor": mapper::TREE_BRANCH_LEFT_ANCHOR },
+                        "right": { "column": 1, "anchor": mapper::TREE_BRANCH_RIGHT_ANCHOR },
+                    });
+                    (sprite, entry)
+                }
+            };
+
+            let result = std::fs::create_dir_all(&dir).map_err(image::ImageError::from)
+                .and_then(|_| sprite.save(dir.join(format!("{}.png", file))));
+            if let Err(error) = result {
+                progress_bar.println(format!("Failed to export {} {} {}", name, file, error));
+                continue;
+            }
+            descriptor.insert(file.to_owned(), entry);
+        }
+        if descriptor.is_empty() {
+            return;
+        }
+
+        let result = std::fs::File::create(dir.join("tree.json"))
+            .map_err(serde_json::Error::io)
+            .and_then(|json| serde_json::to_writer_pretty(json, &descriptor));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} descriptor {}", name, error));
+        }
+    });
+}
+
+// Writes the body frames, surface, slopes and waterfall of every liquid style to liquid/<name>/.
+fn export_liquids(manager: &ResourceManager, liquid: &Path, separate_frames: bool, progress_bar: &ProgressBar) {
+    progress_bar.reset();
+    progress_bar.set_length(registry::LIQUIDS.len() as u64);
+
+    registry::LIQUIDS.par_iter().progress_with(progress_bar.clone()).for_each(|&(id, name, waterfall)| {
+        let sheet = match manager.get_sprite(ResourcePath::Liquid(id)) {
+            Ok(sheet) => sheet,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", name, error)),
+        };
+
+        let dir = liquid.join(name);
+        let result = std::fs::create_dir_all(&dir).map_err(image::ImageError::from).and_then(|_| {
+            match mapper::slice_liquid(&sheet) {
+                Some((body, surface)) => {
+                    export_frames(&body, &[], &dir, "body", separate_frames)?;
+                    surface.save(dir.join("surface.png"))
+                }
+                None => {
+                    progress_bar.println(format!("{} is not a 16 frame liquid sheet, it was copied as is", name));
+                    export_raw(&sheet, &dir, "body")
+                }
+            }
+        });
+        if let Err(error) = result {
+            return progress_bar.println(format!("Failed to export {} {}", name, error));
+        }
+
+        let extras = [("slope", Some(ResourcePath::LiquidSlope(id))), ("waterfall", waterfall.map(ResourcePath::Waterfall))];
+        for (file, path) in extras {
+            let Some(path) = path else {
+                continue;
+            };
+
+            let result = match manager.get_sprite(path) {
+                Ok(sprite) => sprite.save(dir.join(format!("{}.png", file))),
+                Err(error) => {
+                    progress_bar.println(format!("Failed to read {} {} {}", name, file, error));
+                    continue;
+                }
+            };
+            if let Err(error) = result {
+                progress_bar.println(format!("Failed to export {} {} {}", name, file, error));
+            }
+        }
+    });
+}
+
+// Packs every buff icon into buff/atlas.png, with atlas.json mapping each name to its rectangle.
+fn export_buffs(manager: &ResourceManager, buff: &Path, progress_bar: &ProgressBar) {
+    progress_bar.reset();
+    progress_bar.set_length(registry::BUFFS.len() as u64);
+
+    let icons: Vec<(&str, Sprite)> = registry::BUFFS.par_iter().progress_with(progress_bar.clone()).filter_map(|id| {
+        match manager.get_sprite(ResourcePath::Buff(id.0)) {
+            Ok(sprite) => Some((id.1, sprite)),
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", id.1, error));
+                None
+            }
+        }
+    }).collect();
+    if icons.is_empty() {
+        return;
+    }
+
+    let (names, sprites): (Vec<_>, Vec<_>) = icons.into_iter().unzip();
+    let (atlas, rects) = mapper::pack_atlas(&sprites);
+    let index: BTreeMap<_, _> = names.into_iter().zip(rects).collect();
+
+    let result = std::fs::create_dir_all(buff).map_err(image::ImageError::from)
+        .and_then(|_| atlas.save(buff.join("atlas.png")));
+    if let Err(error) = result {
+        return progress_bar.println(format!("Failed to export buff atlas {}", error));
+    }
+
+    let result = std::fs::File::create(buff.join("atlas.json"))
+        .map_err(serde_json::Error::io)
+        .and_then(|json| serde_json::to_writer_pretty(json, &index));
+    if let Err(error) = result {
+        progress_bar.println(format!("Failed to export buff atlas index {}", error));
+    }
+}
+
+// Writes every background to background/<biome>/<layer>.png with a manifest.json per biome
+// listing its layers back to front.
+fn export_backgrounds(manager: &ResourceManager, background: &Path, progress_bar: &ProgressBar) {
+    progress_bar.reset();
+    progress_bar.set_length(registry::BACKGROUNDS.len() as u64);
+
+    let layers: Vec<_> = registry::BACKGROUNDS.par_iter().progress_with(progress_bar.clone()).filter_map(|&(id, biome, layer)| {
+        let file = format!("{:?}.png", layer).to_lowercase();
+        let name = format!("{}/{}", biome, file);
+        let sprite = match manager.get_sprite(ResourcePath::Background(id)) {
+            Ok(sprite) => sprite,
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", name, error));
+                return None;
+            }
+        };
+
+        let result = std::fs::create_dir_all(background.join(biome)).map_err(image::ImageError::from)
+            .and_then(|_| sprite.save(background.join(&name)));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", name, error));
+            return None;
+        }
+
+        Some((biome, layer, serde_json::json!({
+            "file": file,
+            "layer": layer,
+            "scroll_factor": layer.scroll_factor(),
+            "width": sprite.width(),
+            "height": sprite.height(),
+        })))
+    }).collect();
+
+    let mut biomes: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for (biome, layer, entry) in layers {
+        biomes.entry(biome).or_default().push((layer, entry));
+    }
+
+    for (biome, mut layers) in biomes {
+        layers.sort_by_key(|(layer, _)| *layer);
+        let layers: Vec<_> = layers.into_iter().map(|(_, entry)| entry).collect();
+        let result = std::fs::File::create(background.join(biome).join("manifest.json"))
+            .map_err(serde_json::Error::io)
+            .and_then(|json| serde_json::to_writer_pretty(json, &serde_json::json!({ "layers": layers })));
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} manifest {}", biome, error));
+        }
+    }
+}
+
+// Writes the frames of an animation with a name.json descriptor next to them.
+// Sprites with their frames stacked top to bottom, frame counts looked up in frame_table.
+#[allow(clippy::too_many_arguments)]
+fn export_animated(
+    manager: &ResourceManager,
+    ids: &[(u32, &str)],
+    path: fn(u32) -> ResourcePath,
+    frame_table: &[(u32, u32)],
+    padding: u32,
+    dir: &Path,
+    separate_frames: bool,
+    progress_bar: &ProgressBar,
+) {
+    std::fs::create_dir_all(dir).unwrap();
+    progress_bar.reset();
+    progress_bar.set_length(ids.len() as u64);
+
+    ids.par_iter().progress_with(progress_bar.clone()).for_each(|id| {
+        let sprite = match manager.get_sprite(path(id.0)) {
+            Ok(sprite) => sprite,
+            Err(error) => return progress_bar.println(format!("Failed to read {} {}", id.1, error)),
+        };
+
+        let frames = registry::frame_count(frame_table, id.0);
+        if !sprite.height().is_multiple_of(frames) {
+            progress_bar.println(format!("{} is {}px high, which does not split into {} frames", id.1, sprite.height(), frames));
+        }
+
+        let frames = mapper::slice_vertical(&sprite, frames, padding);
+        if let Err(error) = export_frames(&frames, &[], dir, id.1, separate_frames) {
+            progress_bar.println(format!("Failed to export {} {}", id.1, error));
+        }
+    });
+}
+
+fn export_frames(frames: &[Sprite], animations: &[mapper::Animation], dir: &Path, name: &str, separate: bool) -> image::ImageResult<()> {
+    let (frame_width, frame_height) = frames.first().map_or((0, 0), |frame| frame.dimensions());
+    if separate {
+        for (i, frame) in frames.iter().enumerate() {
+            frame.save(dir.join(format!("{}_{}.png", name, i)))?;
+        }
+    } else {
+        mapper::pack_horizontal(frames).save(dir.join(format!("{}.png", name)))?;
+    }
+
+    let sheet = mapper::FrameSheet {
+        frame_width,
+        frame_height,
+        frames: frames.len() as u32,
+        layout: if separate { "separate" } else { "horizontal" },
+        animations: animations.to_vec(),
+    };
+    let json = std::fs::File::create(dir.join(format!("{}.json", name)))?;
+    serde_json::to_writer_pretty(json, &sheet).map_err(std::io::Error::from)?;
+    Ok(())
+}
+
+// Sheets like the 1.4 composite body and player sheets, which are not sliced into frames.
+fn export_raw(sheet: &Sprite, dir: &Path, name: &str) -> image::ImageResult<()> {
+    sheet.save(dir.join(format!("{}.png", name)))?;
+
+    let sheet = mapper::FrameSheet {
+        frame_width: sheet.width(),
+        frame_height: sheet.height(),
+        frames: 1,
+        layout: "raw",
+        animations: Vec::new(),
+    };
+    let json = std::fs::File::create(dir.join(format!("{}.json", name)))?;
+    serde_json::to_writer_pretty(json, &sheet).map_err(std::io::Error::from)?;
+    Ok(())
+}
+
+// Lists the item sprites in the Images directory that ITEMS has no Rustaria name for.
+fn report_unmapped_items(images: &Path, item: &Path, progress_bar: &ProgressBar) {
+    let Ok(entries) = std::fs::read_dir(images) else {
+        return;
+    };
+
+    let mut unmapped: Vec<u32> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            let id = name.to_str()?.strip_prefix("Item_")?.strip_suffix(".xnb")?.parse().ok()?;
+            Some(id).filter(|id| !registry::ITEMS.iter().any(|item| item.0 == *id))
+        })
+        .collect();
+    unmapped.sort_unstable();
+
+    progress_bar.println(format!("{} items have no Rustaria name, see item/unmapped.json", unmapped.len()));
+    let result = std::fs::File::create(item.join("unmapped.json"))
+        .map_err(serde_json::Error::io)
+        .and_then(|json| serde_json::to_writer_pretty(json, &unmapped));
+    if let Err(error) = result {
+        progress_bar.println(format!("Failed to export unmapped.json {}", error));
+    }
+}
+
+// Decodes the soundtrack in Wave Bank.xwb, named after the cues in Sound Bank.xsb.
+fn export_music(content: &Path, music: &Path, progress_bar: &ProgressBar) {
+    let wave_bank_path = content.join("Wave Bank.xwb");
+    if !wave_bank_path.is_file() {
+        return;
+    }
+
+    let mut wave_bank = match xact::WaveBank::open(&wave_bank_path) {
+        Ok(wave_bank) => wave_bank,
+        Err(error) => return progress_bar.println(format!("Failed to read Wave Bank.xwb {}", error)),
+    };
+    let track_names = match xact::SoundBank::open(&content.join("Sound Bank.xsb")) {
+        Ok(sound_bank) => sound_bank.track_names(&wave_bank.name),
+        Err(error) => {
+            progress_bar.println(format!("Failed to read Sound Bank.xsb {}, tracks keep their index", error));
+            HashMap::new()
+        }
+    };
+
+    std::fs::create_dir_all(music).unwrap();
+    progress_bar.reset();
+    progress_bar.set_length(wave_bank.entries.len() as u64);
+    progress_bar.set_message("Music");
+
+    let mut report = Vec::new();
+    for index in 0..wave_bank.entries.len() {
+        progress_bar.inc(1);
+        let entry = wave_bank.entries[index].clone();
+        let name = track_names.get(&(index as u16)).cloned()
+            .or(entry.name)
+            .unwrap_or_else(|| format!("track_{}", index))
+            .replace(['/', '\\', ':'], "_");
+
+        let wave = match wave_bank.read_wave(index) {
+            Ok(wave) => wave,
+            Err(error) => {
+                progress_bar.println(format!("Failed to read {} {}", name, error));
+                continue;
+            }
+        };
+
+        let file = format!("{}.{}", name, wave.extension());
+        let result = std::fs::File::create(music.join(&file)).and_then(|out| {
+            let mut out = std::io::BufWriter::new(out);
+            match &wave {
+                xact::Wave::Pcm(sound) => sound.write_wav(&mut out),
+                xact::Wave::Xwma(data) | xact::Wave::Raw(data) => out.write_all(data),
+            }
+        });
+        if let Err(error) = result {
+            progress_bar.println(format!("Failed to export {} {}", name, error));
+            continue;
+        }
+
+        let decoded = matches!(wave, xact::Wave::Pcm(_));
+        if !decoded {
+            progress_bar.println(format!("{} is {:?} and was written undecoded to {}", name, entry.format.codec, file));
+        }
+        report.push(serde_json::json!({
+            "index": index,
+            "name": name,
+            "file": file,
+            "codec": entry.format.codec,
+            "channels": entry.format.channels,
+            "sample_rate": entry.format.sample_rate,
+            "duration": entry.duration as f64 / entry.format.sample_rate.max(1) as f64,
+            "decoded": decoded,
+        }));
+    }
+
+    let result = std::fs::File::create(music.join("report.json"))
+        .map_err(serde_json::Error::io)
+        .and_then(|json| serde_json::to_writer_pretty(json, &report));
+    if let Err(error) = result {
+        progress_bar.println(format!("Failed to export report.json {}", error));
+    }
+}
+
+fn get_levels(manager: &ResourceManager, path: ResourcePath, mipmaps: bool) -> Result<Vec<Sprite>, XNBError> {
+    if mipmaps {
+        manager.get_mipmaps(path)
+    } else {
+        manager.get_sprite(path).map(|sprite| vec![sprite])
+    }
+}
+
+fn mip_file_name(name: &str, level: usize) -> String {
+    if level == 0 {
+        format!("{}.png", name)
+    } else {
+        format!("{}.mip{}.png", name, level)
+    }
+}
+
 pub fn new_progress_bar() -> ProgressBar {
     let header = "{msg} [{prefix}".to_owned() + "{percent}% ";
     let footer = "{eta} {pos}".to_owned() + &*style("/").fg(Color::White).to_string() + "{len}";
 
     let format = header + "{wide_bar:.cyan}\x1b[m] " + &*footer;
-    let progress_bar = ProgressBar::new(1).with_style(
+    ProgressBar::new(1).with_style(
         ProgressStyle::default_bar()
-            .template(&*format)
+            .template(&format)
             .progress_chars("##-"),
-    );
-
-
-    progress_bar
+    )
 }
\ No newline at end of file
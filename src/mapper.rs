@@ -1,28 +1,68 @@
-use crate::asset::Sprite;
+use serde::Serialize;
+
+use oxidizer::asset::Sprite;
+use oxidizer::xnb::Rectangle;
+
+use crate::registry::Furniture;
+
+/// Describes how the frames of an animated sprite were written.
+#[derive(Serialize)]
+pub struct FrameSheet {
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frames: u32,
+    /// "horizontal" for a single sheet, frame 0 leftmost, "separate" for name_0.png, name_1.png, ...
+    /// or "raw" for a sheet whose layout isn't known, written unchanged as a single frame.
+    pub layout: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<Animation>,
+}
+
+/// A run of consecutive frames in a FrameSheet.
+#[derive(Serialize, Clone)]
+pub struct Animation {
+    pub name: &'static str,
+    pub start: u32,
+    pub frames: u32,
+}
+
+// Player sheets and every equip drawn on the player share 20 frames stacked top to bottom.
+const PLAYER_FRAMES: u32 = 20;
+// Terraria frames of each animation, in the order Rustaria lays them out.
+const PLAYER_ANIMATIONS: [(&str, u32, u32); 4] = [
+    ("idle", 0, 1),
+    ("walk", 6, 14),
+    ("jump", 5, 1),
+    ("use", 1, 4),
+];
 
 pub struct Layout {
     tile_size: u32,
     tile_padding: u32,
+    // Mip level the sprite belongs to. Sizes and positions are always given for level 0.
+    level: u32,
     sprite: Sprite,
 }
 
 impl Layout {
     pub fn remap(&mut self, source: &Layout, from: (u32, u32), to: (u32, u32)) {
-        let scale = source.tile_size as f32 / self.tile_size as f32;
-        if scale != scale.round() {
+        // Either size has to be a multiple of the other, upscaling repeats pixels.
+        if !source.tile_size.is_multiple_of(self.tile_size) && !self.tile_size.is_multiple_of(source.tile_size) {
             panic!("The quotient of the source by the scale is not equal to the target. ");
         }
-        let scale = scale as u32;
 
-        let source_offset = (source.tile_size + source.tile_padding);
+        let source_offset = source.tile_size + source.tile_padding;
         let from_pos = (from.0 * source_offset, from.1 * source_offset);
 
-        let target_offset = (self.tile_size + self.tile_padding);
+        let target_offset = self.tile_size + self.tile_padding;
         let to_pos = (to.0 * target_offset, to.1 * target_offset);
 
-        for y in 0..self.tile_size {
-            for x in 0..self.tile_size {
-                self.sprite.put_pixel(to_pos.0 + x, to_pos.1 + y, *source.sprite.get_pixel(from_pos.0 + (x * scale), from_pos.1 + (y * scale)));
+        let level = self.level;
+        for y in 0..(self.tile_size >> level).max(1) {
+            for x in 0..(self.tile_size >> level).max(1) {
+                let from_x = (from_pos.0 + (x << level) * source.tile_size / self.tile_size) >> level;
+                let from_y = (from_pos.1 + (y << level) * source.tile_size / self.tile_size) >> level;
+                self.sprite.put_pixel((to_pos.0 >> level) + x, (to_pos.1 >> level) + y, *source.sprite.get_pixel(from_x, from_y));
             }
         }
     }
@@ -35,9 +75,31 @@ impl Layout {
 // 3 f h r r
 //    flat ^
 
-pub fn remap_tile(sprite: Sprite) -> Sprite {
-    let source = Layout { tile_size: 16, tile_padding: 2, sprite };
-    let mut target = Layout { tile_size: 8, tile_padding: 0, sprite: Sprite::new(96, 32) };
+pub fn remap_tile(sprite: Sprite, level: u32) -> Sprite {
+    let source = Layout { tile_size: 16, tile_padding: 2, level, sprite };
+    let mut target = Layout { tile_size: 8, tile_padding: 0, level, sprite: level_sprite(96, 32, level) };
+    for (from, to) in tile_frames() {
+        target.remap(&source, from, to);
+    }
+
+    target.sprite
+}
+
+// Expands a Rustaria tile sheet back into Terraria's 18px stride layout.
+pub fn unmap_tile(sprite: Sprite) -> Sprite {
+    let source = Layout { tile_size: 8, tile_padding: 0, level: 0, sprite };
+    let mut target = Layout { tile_size: 16, tile_padding: 2, level: 0, sprite: Sprite::new(18 * 16, 18 * 15) };
+    for (from, to) in tile_frames() {
+        target.remap(&source, to, from);
+    }
+
+    target.sprite
+}
+
+// Terraria frame -> Rustaria frame
+#[allow(clippy::identity_op)]
+fn tile_frames() -> Vec<((u32, u32), (u32, u32))> {
+    let mut frames = Vec::new();
 
     // v = variant
     for v_raw in 0..3 {
@@ -46,50 +108,71 @@ pub fn remap_tile(sprite: Sprite) -> Sprite {
         // Corners. Top left \n top right \n bottom left \n bottom right
         // cor
         // cor
-        target.remap(&source, (v * 2, 3),       (0 + variant_offset, 0));
-        target.remap(&source, (v * 2, 4),       (0 + variant_offset, 1));
-        target.remap(&source, ((v * 2) + 1, 3), (1 + variant_offset, 0));
-        target.remap(&source, ((v * 2) + 1, 4), (1 + variant_offset, 1));
+        frames.push(((v * 2, 3),       (0 + variant_offset, 0)));
+        frames.push(((v * 2, 4),       (0 + variant_offset, 1)));
+        frames.push((((v * 2) + 1, 3), (1 + variant_offset, 0)));
+        frames.push((((v * 2) + 1, 4), (1 + variant_offset, 1)));
 
         // Left row
         // full \n vertical \n horizontal \n standalone
-        target.remap(&source, (v + 1, 1), (0 + variant_offset, 3));
-        target.remap(&source, (5, v),     (0 + variant_offset, 2));
-        target.remap(&source, (6 + v, 4), (1 + variant_offset, 3));
-        target.remap(&source, (9 + v, 3), (1 + variant_offset, 2));
+        frames.push(((v + 1, 1), (0 + variant_offset, 3)));
+        frames.push(((5, v),     (0 + variant_offset, 2)));
+        frames.push(((6 + v, 4), (1 + variant_offset, 3)));
+        frames.push(((9 + v, 3), (1 + variant_offset, 2)));
 
         // Same block surrounds except direction.
-        target.remap(&source, (v + 1, 0), (3 + variant_offset, 0));
-        target.remap(&source, (v + 1, 2), (3 + variant_offset, 1));
-        target.remap(&source, (0, v),     (3 + variant_offset, 2));
-        target.remap(&source, (4, v),     (3 + variant_offset, 3));
+        frames.push(((v + 1, 0), (3 + variant_offset, 0)));
+        frames.push(((v + 1, 2), (3 + variant_offset, 1)));
+        frames.push(((0, v),     (3 + variant_offset, 2)));
+        frames.push(((4, v),     (3 + variant_offset, 3)));
 
         // Air block surrounds. Opposite is same block.
-        target.remap(&source, (6 + v, 0), (2 + variant_offset, 0));
-        target.remap(&source, (6 + v, 3), (2 + variant_offset, 1));
-        target.remap(&source, (9, v),     (2 + variant_offset, 2));
-        target.remap(&source, (12, v),    (2 + variant_offset, 3));
+        frames.push(((6 + v, 0), (2 + variant_offset, 0)));
+        frames.push(((6 + v, 3), (2 + variant_offset, 1)));
+        frames.push(((9, v),     (2 + variant_offset, 2)));
+        frames.push(((12, v),    (2 + variant_offset, 3)));
     };
 
+    frames
+}
+
+pub fn remap_wall(sprite: Sprite, level: u32) -> Sprite {
+    let source = Layout { tile_size: 32, tile_padding: 4, level, sprite };
+    let mut target = Layout { tile_size: 16, tile_padding: 0, level, sprite: level_sprite(8 * 6, 8 * 2, level) };
+    for (from, to) in wall_frames() {
+        target.remap(&source, from, to);
+    }
+
     target.sprite
 }
 
-pub fn remap_wall(sprite: Sprite) -> Sprite {
-    let source = Layout { tile_size: 32, tile_padding: 4, sprite };
-    let mut target = Layout { tile_size: 16, tile_padding: 0, sprite: Sprite::new(8 * 6, 8 * 2) };
+// Expands a Rustaria wall sheet back into Terraria's 36px stride layout.
+pub fn unmap_wall(sprite: Sprite) -> Sprite {
+    let source = Layout { tile_size: 16, tile_padding: 0, level: 0, sprite };
+    let mut target = Layout { tile_size: 32, tile_padding: 4, level: 0, sprite: Sprite::new(36 * 13, 36 * 5) };
+    for (from, to) in wall_frames() {
+        target.remap(&source, to, from);
+    }
+
+    target.sprite
+}
+
+// Terraria frame -> Rustaria frame
+fn wall_frames() -> Vec<((u32, u32), (u32, u32))> {
+    let mut frames = Vec::new();
     for v_raw in 0..3 {
         let v = v_raw as u32;
         // 3x3 grid copy
-        target.remap(&source, (9 + v, 3), (v, 0));
+        frames.push(((9 + v, 3), (v, 0)));
     };
 
-    target.sprite
+    frames
 }
 
-
+#[allow(dead_code, clippy::identity_op)]
 pub fn remap_wall_full(sprite: Sprite) -> Sprite {
-    let source = Layout { tile_size: 32, tile_padding: 4, sprite };
-    let mut target = Layout { tile_size: 16, tile_padding: 0, sprite: Sprite::new(32 * 3 * 2, 40 * 2) };
+    let source = Layout { tile_size: 32, tile_padding: 4, level: 0, sprite };
+    let mut target = Layout { tile_size: 16, tile_padding: 0, level: 0, sprite: Sprite::new(32 * 3 * 2, 40 * 2) };
 
     // v = variant
     for v_raw in 0..3 {
@@ -119,3 +202,274 @@ pub fn remap_wall_full(sprite: Sprite) -> Sprite {
 
     target.sprite
 }
+
+// Splits a sheet of equally sized frames stacked top to bottom, `padding` being the
+// rows under each frame that separate it from the next one. A single frame has nothing
+// to be separated from and is kept whole.
+pub fn slice_vertical(sprite: &Sprite, frames: u32, padding: u32) -> Vec<Sprite> {
+    let frames = frames.clamp(1, sprite.height().max(1));
+    let stride = sprite.height() / frames;
+    let padding = if frames > 1 { padding } else { 0 };
+    let height = stride.saturating_sub(padding).max(1);
+    (0..frames)
+        .map(|frame| image::imageops::crop_imm(sprite, 0, frame * stride, sprite.width(), height.min(stride)).to_image())
+        .collect()
+}
+
+// Lays equally sized frames out left to right.
+pub fn pack_horizontal(frames: &[Sprite]) -> Sprite {
+    let (width, height) = frames.first().map_or((0, 0), |frame| frame.dimensions());
+    let mut sheet = Sprite::new(width * frames.len() as u32, height);
+    for (i, frame) in frames.iter().enumerate() {
+        image::imageops::replace(&mut sheet, frame, i as u32 * width, 0);
+    }
+    sheet
+}
+
+// Reorders a 20 frame player sheet into idle, walk, jump and use. Sheets in any other
+// layout, like the composite body sheets of 1.4, give None.
+pub fn remap_player(sprite: &Sprite) -> Option<(Vec<Sprite>, Vec<Animation>)> {
+    let frame_height = sprite.height() / PLAYER_FRAMES;
+    if frame_height == 0 || !sprite.height().is_multiple_of(PLAYER_FRAMES) || sprite.width() > frame_height {
+        return None;
+    }
+
+    let source = slice_vertical(sprite, PLAYER_FRAMES, 0);
+    let mut frames = Vec::new();
+    let mut animations = Vec::new();
+    for (name, start, count) in PLAYER_ANIMATIONS {
+        animations.push(Animation { name, start: frames.len() as u32, frames: count });
+        frames.extend_from_slice(&source[start as usize..(start + count) as usize]);
+    }
+    Some((frames, animations))
+}
+
+// Packs sprites into rows of a roughly square atlas, tallest first. Returns where each sprite went,
+// in the order they were given.
+pub fn pack_atlas(sprites: &[Sprite]) -> (Sprite, Vec<Rectangle>) {
+    let area: u32 = sprites.iter().map(|sprite| sprite.width() * sprite.height()).sum();
+    let widest = sprites.iter().map(|sprite| sprite.width()).max().unwrap_or(0);
+    let width = ((area as f64).sqrt().ceil() as u32).max(widest);
+
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sprites[i].height()));
+
+    let mut rects = vec![Rectangle { x: 0, y: 0, width: 0, height: 0 }; sprites.len()];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for i in order {
+        let (sprite_width, sprite_height) = sprites[i].dimensions();
+        if x + sprite_width > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        rects[i] = Rectangle { x: x as i32, y: y as i32, width: sprite_width as i32, height: sprite_height as i32 };
+        x += sprite_width;
+        row_height = row_height.max(sprite_height);
+    }
+
+    let mut atlas = Sprite::new(width, y + row_height);
+    for (sprite, rect) in sprites.iter().zip(&rects) {
+        image::imageops::replace(&mut atlas, sprite, rect.x as u32, rect.y as u32);
+    }
+    (atlas, rects)
+}
+
+// Trunk frames of tile 5 by column and first row in Terraria's 22px stride, every frame has
+// three variants in the rows below it. Rustaria lays them out left to right, variants top to bottom.
+pub const TREE_FRAMES: [(&str, u32, u32); 12] = [
+    ("trunk", 0, 0),
+    ("trunk_bark_left", 0, 3),
+    ("trunk_bark_right", 1, 0),
+    ("trunk_branch_left", 3, 0),
+    ("trunk_branch_right", 4, 3),
+    ("branch_left", 3, 3),
+    ("branch_right", 4, 0),
+    ("root_left", 2, 6),
+    ("root_right", 1, 6),
+    ("base", 4, 6),
+    ("top", 1, 9),
+    ("top_broken", 0, 9),
+];
+pub const TREE_VARIANTS: u32 = 3;
+pub const TREE_FRAME_SIZE: u32 = 20;
+// Padding between the frames of every tree sheet.
+pub const TREE_PADDING: u32 = 2;
+// Trunk frames are wider than the 16px tile, this point of the frame is drawn at its top left corner.
+pub const TREE_TRUNK_ANCHOR: [u32; 2] = [2, 0];
+// Tops lie side by side, the anchor is where the trunk meets them.
+pub const TREE_TOP_SIZE: u32 = 80;
+pub const TREE_TOP_ANCHOR: [u32; 2] = [32, 64];
+// Left branches fill the first column and right branches the second, anchored at the trunk.
+pub const TREE_BRANCH_SIZE: u32 = 40;
+pub const TREE_BRANCH_LEFT_ANCHOR: [u32; 2] = [24, 12];
+pub const TREE_BRANCH_RIGHT_ANCHOR: [u32; 2] = [0, 12];
+
+/// The sheets a tree is drawn from.
+#[derive(Copy, Clone)]
+pub enum TreePart {
+    Trunk,
+    Tops,
+    Branches,
+}
+
+pub const TREE_PARTS: [TreePart; 3] = [TreePart::Trunk, TreePart::Tops, TreePart::Branches];
+
+impl TreePart {
+    pub fn name(self) -> &'static str {
+        match self {
+            TreePart::Trunk => "trunk",
+            TreePart::Tops => "tops",
+            TreePart::Branches => "branches",
+        }
+    }
+}
+
+// Keeps Terraria's 20px tree frames, only dropping the padding between them. Sheets too small
+// to hold every frame give None.
+pub fn remap_tree(sprite: Sprite) -> Option<Sprite> {
+    let stride = TREE_FRAME_SIZE + TREE_PADDING;
+    let (columns, rows) = TREE_FRAMES.iter()
+        .fold((0, 0), |(columns, rows), &(_, column, row)| (columns.max(column + 1), rows.max(row + TREE_VARIANTS)));
+    if sprite.width() < columns * stride - TREE_PADDING || sprite.height() < rows * stride - TREE_PADDING {
+        return None;
+    }
+
+    let source = Layout { tile_size: TREE_FRAME_SIZE, tile_padding: TREE_PADDING, level: 0, sprite };
+    let target_sprite = Sprite::new(TREE_FRAME_SIZE * TREE_FRAMES.len() as u32, TREE_FRAME_SIZE * TREE_VARIANTS);
+    let mut target = Layout { tile_size: TREE_FRAME_SIZE, tile_padding: 0, level: 0, sprite: target_sprite };
+    for (i, &(_, column, row)) in TREE_FRAMES.iter().enumerate() {
+        for variant in 0..TREE_VARIANTS {
+            target.remap(&source, (column, row + variant), (i as u32, variant));
+        }
+    }
+
+    Some(target.sprite)
+}
+
+// Cuts every style of a furniture sheet into its animation frames, each frame a standalone
+// image with the padding between tiles removed. Styles and frames past the end of the sheet are left out.
+pub fn slice_furniture(sprite: &Sprite, furniture: &Furniture) -> Vec<Vec<Sprite>> {
+    let stride = furniture.tile_width + 2;
+    let rows_height: u32 = furniture.coordinate_heights.iter().sum();
+    let (width, height) = (furniture.width * stride - 2, rows_height + furniture.height() * 2 - 2);
+    let fits = |x: u32, y: u32| x + width <= sprite.width() && y + height <= sprite.height();
+
+    let mut styles = Vec::new();
+    for style in 0..furniture.styles {
+        let style_x = style * furniture.style_stride.0;
+        let style_y = style * furniture.style_stride.1;
+        let frames: Vec<Sprite> = (0..furniture.frames)
+            .map(|frame| (style_x + frame * furniture.frame_stride.0, style_y + frame * furniture.frame_stride.1))
+            .take_while(|&(x, y)| fits(x, y))
+            .map(|(x, y)| {
+                let mut image = Sprite::new(furniture.width * furniture.tile_width, rows_height);
+                let (mut source_y, mut target_y) = (y, 0);
+                for &row_height in furniture.coordinate_heights {
+                    for tile_x in 0..furniture.width {
+                        let tile = image::imageops::crop_imm(sprite, x + tile_x * stride, source_y, furniture.tile_width, row_height);
+                        image::imageops::replace(&mut image, &tile.to_image(), tile_x * furniture.tile_width, target_y);
+                    }
+                    source_y += row_height + 2;
+                    target_y += row_height;
+                }
+                image
+            })
+            .collect();
+
+        if frames.is_empty() {
+            break;
+        }
+        styles.push(frames);
+    }
+    styles
+}
+
+// Liquid sheets hold 16 animation frames of 80px stacked top to bottom, followed by the surface.
+const LIQUID_FRAMES: u32 = 16;
+const LIQUID_FRAME_HEIGHT: u32 = 80;
+
+// Splits a liquid sheet into its body frames and surface. Sheets from versions with another
+// layout give None.
+pub fn slice_liquid(sprite: &Sprite) -> Option<(Vec<Sprite>, Sprite)> {
+    let body_height = LIQUID_FRAMES * LIQUID_FRAME_HEIGHT;
+    if sprite.height() <= body_height {
+        return None;
+    }
+
+    let body = image::imageops::crop_imm(sprite, 0, 0, sprite.width(), body_height).to_image();
+    let surface = image::imageops::crop_imm(sprite, 0, body_height, sprite.width(), sprite.height() - body_height).to_image();
+    Some((slice_vertical(&body, LIQUID_FRAMES, 0), surface))
+}
+
+fn level_sprite(width: u32, height: u32, level: u32) -> Sprite {
+    Sprite::new((width >> level).max(1), (height >> level).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    const PADDING: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+    #[test]
+    fn slice_furniture_crops_rows_at_their_height() {
+        let table = Furniture {
+            id: 14,
+            name: "table",
+            width: 3,
+            origin: (1, 1),
+            tile_width: 16,
+            coordinate_heights: &[16, 18],
+            styles: 1,
+            style_stride: (0, 0),
+            frames: 1,
+            frame_stride: (0, 0),
+        };
+
+        // Every tile is filled with its column and row, everything else is padding.
+        let mut sheet = Sprite::from_pixel(3 * 18 - 2, 16 + 2 + 18, PADDING);
+        for (row, (top, height)) in [(0, 16), (18, 18)].into_iter().enumerate() {
+            for column in 0..3 {
+                for y in top..top + height {
+                    for x in column * 18..column * 18 + 16 {
+                        sheet.put_pixel(x, y, Rgba([column as u8, row as u8, 0, 255]));
+                    }
+                }
+            }
+        }
+
+        let styles = slice_furniture(&sheet, &table);
+        assert_eq!(styles.len(), 1);
+        let frame = &styles[0][0];
+        assert_eq!(frame.dimensions(), (48, 34));
+        assert!(frame.pixels().all(|&pixel| pixel != PADDING));
+        assert_eq!(frame.get_pixel(17, 15).0, [1, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(17, 16).0, [1, 1, 0, 255]);
+        assert_eq!(frame.get_pixel(47, 33).0, [2, 1, 0, 255]);
+    }
+
+    #[test]
+    fn slice_vertical_drops_padding_between_frames_only() {
+        let mut sheet = Sprite::from_pixel(4, 2 * 12, PADDING);
+        for frame in 0..2 {
+            for y in frame * 12..frame * 12 + 10 {
+                for x in 0..4 {
+                    sheet.put_pixel(x, y, Rgba([frame as u8, 0, 0, 255]));
+                }
+            }
+        }
+        let frames = slice_vertical(&sheet, 2, 2);
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.dimensions() == (4, 10)));
+        assert!(frames.iter().all(|frame| frame.pixels().all(|&pixel| pixel != PADDING)));
+
+        // Single frame items have no gap, their bottom rows are part of the sprite.
+        let item = Sprite::from_pixel(4, 12, Rgba([1, 2, 3, 255]));
+        let frames = slice_vertical(&item, 1, 2);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0], item);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use crate::asset::Sprite;
+use crate::effect::Effect;
+use crate::font::Font;
+use crate::sound::{Sound, WaveFormat};
+use crate::surface::{self, AlphaMode, SurfaceFormat};
+use crate::xnb::{ContentReader, FormatVersion, Platform, Rectangle, XNBError, XnbInput, XnbValue};
+
+/// Reads the objects written by one of the content pipeline's type writers.
+/// `arguments` are the generic arguments of the reader, `ListReader`1[[System.Int32]]` gets `System.Int32`.
+pub trait TypeReader: Send + Sync {
+    fn read(&self, reader: &mut ContentReader, arguments: &[String]) -> Result<XnbValue, XNBError>;
+}
+
+type ReadFn = fn(&mut XnbInput) -> Result<XnbValue, XNBError>;
+
+/// A field of a type read by the ReflectiveReader.
+#[derive(Clone)]
+pub struct ReflectiveField {
+    pub type_name: String,
+    /// Written with WriteSharedResource instead of inline.
+    pub shared_resource: bool,
+}
+
+/// Type readers by name, without generic arguments or assembly qualifiers.
+/// `Microsoft.Xna.Framework.Content.ListReader`1[[System.Int32, mscorlib]]` is registered
+/// as `Microsoft.Xna.Framework.Content.ListReader`1`.
+pub struct TypeReaderRegistry {
+    readers: HashMap<String, Box<dyn TypeReader>>,
+    // Value types are written without the type reader index in front of them.
+    value_types: HashMap<String, String>,
+    reflective_types: HashMap<String, Vec<ReflectiveField>>,
+}
+
+impl Default for TypeReaderRegistry {
+    fn default() -> TypeReaderRegistry {
+        TypeReaderRegistry::new()
+    }
+}
+
+impl TypeReaderRegistry {
+    pub fn new() -> TypeReaderRegistry {
+        TypeReaderRegistry {
+            readers: HashMap::new(),
+            value_types: HashMap::new(),
+            reflective_types: HashMap::new(),
+        }
+    }
+
+    /// Every reader oxidizer knows about.
+    pub fn builtin(alpha: AlphaMode) -> TypeReaderRegistry {
+        let mut registry = TypeReaderRegistry::new();
+        registry.register("Microsoft.Xna.Framework.Content.Texture2DReader", Texture2DReader { alpha });
+        registry.register("Microsoft.Xna.Framework.Content.SoundEffectReader", SoundEffectReader);
+        registry.register("Microsoft.Xna.Framework.Content.SpriteFontReader", SpriteFontReader);
+        registry.register("Microsoft.Xna.Framework.Content.EffectReader", EffectReader);
+
+        let primitives: [(&str, &str, ReadFn); 20] = [
+            ("BooleanReader", "System.Boolean", |input| Ok(XnbValue::Bool(input.get_u8()? != 0))),
+            ("ByteReader", "System.Byte", |input| Ok(XnbValue::UInt(input.get_u8()? as u64))),
+            ("SByteReader", "System.SByte", |input| Ok(XnbValue::Int(input.get_i8()? as i64))),
+            ("Int16Reader", "System.Int16", |input| Ok(XnbValue::Int(input.get_i16_le()? as i64))),
+            ("UInt16Reader", "System.UInt16", |input| Ok(XnbValue::UInt(input.get_u16_le()? as u64))),
+            ("Int32Reader", "System.Int32", |input| Ok(XnbValue::Int(input.get_i32_le()? as i64))),
+            ("UInt32Reader", "System.UInt32", |input| Ok(XnbValue::UInt(input.get_u32_le()? as u64))),
+            ("Int64Reader", "System.Int64", |input| Ok(XnbValue::Int(input.get_i64_le()?))),
+            ("UInt64Reader", "System.UInt64", |input| Ok(XnbValue::UInt(input.get_u64_le()?))),
+            ("SingleReader", "System.Single", |input| Ok(XnbValue::Float(input.get_f32_le()? as f64))),
+            ("DoubleReader", "System.Double", |input| Ok(XnbValue::Float(input.get_f64_le()?))),
+            ("CharReader", "System.Char", |input| Ok(XnbValue::Char(input.get_char()?))),
+            ("Vector2Reader", "Microsoft.Xna.Framework.Vector2", |input| read_vector(input, 2)),
+            ("Vector3Reader", "Microsoft.Xna.Framework.Vector3", |input| read_vector(input, 3)),
+            ("Vector4Reader", "Microsoft.Xna.Framework.Vector4", |input| read_vector(input, 4)),
+            ("QuaternionReader", "Microsoft.Xna.Framework.Quaternion", |input| read_vector(input, 4)),
+            ("RectangleReader", "Microsoft.Xna.Framework.Rectangle", |input| Ok(XnbValue::Rect(read_rectangle(input)?))),
+            ("PointReader", "Microsoft.Xna.Framework.Point", |input| Ok(XnbValue::Point([input.get_i32_le()?, input.get_i32_le()?]))),
+            ("ColorReader", "Microsoft.Xna.Framework.Color", |input| Ok(XnbValue::Color([input.get_u8()?, input.get_u8()?, input.get_u8()?, input.get_u8()?]))),
+            ("TimeSpanReader", "System.TimeSpan", |input| Ok(XnbValue::Int(input.get_i64_le()?))),
+        ];
+        for (reader, target, read) in primitives {
+            let reader = format!("Microsoft.Xna.Framework.Content.{}", reader);
+            registry.register(&reader, PrimitiveReader(read));
+            registry.register_value_type(target, &reader);
+        }
+
+        registry.register("Microsoft.Xna.Framework.Content.StringReader", PrimitiveReader(|input| Ok(XnbValue::String(input.get_string()?))));
+        registry.register("Microsoft.Xna.Framework.Content.ListReader`1", ListReader);
+        registry.register("Microsoft.Xna.Framework.Content.ArrayReader`1", ListReader);
+        registry.register("Microsoft.Xna.Framework.Content.DictionaryReader`2", DictionaryReader);
+        registry.register("Microsoft.Xna.Framework.Content.NullableReader`1", NullableReader);
+        registry.register_value_type("System.Nullable`1", "Microsoft.Xna.Framework.Content.NullableReader`1");
+        registry.register("Microsoft.Xna.Framework.Content.ReflectiveReader`1", ReflectiveReader);
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, reader: impl TypeReader + 'static) {
+        self.readers.insert(normalize_reader_name(name).to_owned(), Box::new(reader));
+    }
+
+    /// Marks `type_name` as a value type read by `reader` when it shows up inside another object.
+    pub fn register_value_type(&mut self, type_name: &str, reader: &str) {
+        self.value_types.insert(normalize_reader_name(type_name).to_owned(), normalize_reader_name(reader).to_owned());
+    }
+
+    /// Describes the fields of a type written by the ReflectiveWriter, base class fields first.
+    pub fn register_reflective_type(&mut self, type_name: &str, fields: Vec<ReflectiveField>) {
+        self.reflective_types.insert(normalize_reader_name(type_name).to_owned(), fields);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn TypeReader> {
+        self.readers.get(normalize_reader_name(name)).map(|reader| reader.as_ref())
+    }
+
+    /// The reader of a value type, `None` for reference types.
+    pub fn get_value_type(&self, type_name: &str) -> Option<&dyn TypeReader> {
+        // System.Int32[] is an array, not an Int32.
+        if strip_assembly(type_name).ends_with("[]") {
+            return None;
+        }
+        self.get(self.value_types.get(normalize_reader_name(type_name))?)
+    }
+}
+
+pub fn normalize_reader_name(name: &str) -> &str {
+    let end = name.find(['[', ',']).unwrap_or(name.len());
+    name[..end].trim()
+}
+
+/// Splits `Name`2[[A, Assembly],[B, Assembly]], Assembly` into `Name`2` and `[A, B]`.
+pub fn parse_type_name(name: &str) -> (&str, Vec<String>) {
+    let base = normalize_reader_name(name);
+    let rest = &name[base.len()..];
+    if !rest.starts_with("[[") {
+        return (base, Vec::new());
+    }
+
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => {
+                depth += 1;
+                if depth == 2 {
+                    start = i + 1;
+                }
+            }
+            ']' => {
+                depth -= 1;
+                if depth == 1 {
+                    arguments.push(strip_assembly(&rest[start..i]).to_owned());
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (base, arguments)
+}
+
+// Drops the assembly qualifier while keeping the generic arguments of the type.
+fn strip_assembly(name: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in name.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => return name[..i].trim(),
+            _ => {}
+        }
+    }
+    name.trim()
+}
+
+struct PrimitiveReader(ReadFn);
+
+impl TypeReader for PrimitiveReader {
+    fn read(&self, reader: &mut ContentReader, _: &[String]) -> Result<XnbValue, XNBError> {
+        (self.0)(&mut reader.input)
+    }
+}
+
+pub struct Texture2DReader {
+    pub alpha: AlphaMode,
+}
+
+impl TypeReader for Texture2DReader {
+    fn read(&self, reader: &mut ContentReader, _: &[String]) -> Result<XnbValue, XNBError> {
+        Ok(XnbValue::Texture(read_texture(reader, self.alpha)?))
+    }
+}
+
+pub struct SoundEffectReader;
+
+impl TypeReader for SoundEffectReader {
+    fn read(&self, reader: &mut ContentReader, _: &[String]) -> Result<XnbValue, XNBError> {
+        let input = &mut reader.input;
+        let format_size = input.get_u32_le()?;
+        let format = WaveFormat::read(input, format_size as usize)?;
+        let data_size = input.get_u32_le()?;
+        let data = input.read_bytes(data_size as usize)?;
+        let loop_start = input.get_u32_le()?;
+        let loop_length = input.get_u32_le()?;
+        let _duration = input.get_u32_le()?;
+
+        Ok(XnbValue::Sound(Sound { format, data, loop_start, loop_length }))
+    }
+}
+
+pub struct SpriteFontReader;
+
+impl TypeReader for SpriteFontReader {
+    fn read(&self, reader: &mut ContentReader, _: &[String]) -> Result<XnbValue, XNBError> {
+        let texture = match reader.read_object()? {
+            XnbValue::Texture(levels) => levels.into_iter().next(),
+            _ => None,
+        };
+        let texture = texture.ok_or(XNBError::UnexpectedObject("Texture2D"))?;
+
+        // Every nested object is prefixed by the index of its type reader.
+        let input = &mut reader.input;
+        input.get_varint()?;
+        let glyphs = read_list(input, read_rectangle)?;
+        input.get_varint()?;
+        let cropping = read_list(input, read_rectangle)?;
+        input.get_varint()?;
+        let characters = read_list(input, XnbInput::get_char)?;
+        let line_spacing = input.get_i32_le()?;
+        let spacing = input.get_f32_le()?;
+        input.get_varint()?;
+        let kerning = read_list(input, |input| Ok([input.get_f32_le()?, input.get_f32_le()?, input.get_f32_le()?]))?;
+        let default_character = if input.get_u8()? != 0 { Some(input.get_char()?) } else { None };
+
+        Ok(XnbValue::Font(Font { texture, glyphs, cropping, characters, line_spacing, spacing, kerning, default_character }))
+    }
+}
+
+pub struct EffectReader;
+
+impl TypeReader for EffectReader {
+    fn read(&self, reader: &mut ContentReader, _: &[String]) -> Result<XnbValue, XNBError> {
+        let size = reader.input.get_u32_le()?;
+        let bytecode = reader.input.read_bytes(size as usize)?;
+        Ok(XnbValue::Effect(Effect::new(bytecode)?))
+    }
+}
+
+// Lists and arrays share the same layout.
+pub struct ListReader;
+
+impl TypeReader for ListReader {
+    fn read(&self, reader: &mut ContentReader, arguments: &[String]) -> Result<XnbValue, XNBError> {
+        let element = arguments.first().ok_or(XNBError::UnexpectedObject("generic argument"))?;
+        let count = reader.input.get_u32_le()?;
+        let mut values = Vec::with_capacity(capacity(&reader.input, count));
+        for _ in 0..count {
+            values.push(reader.read_value(element)?);
+        }
+        Ok(XnbValue::Vec(values))
+    }
+}
+
+pub struct DictionaryReader;
+
+impl TypeReader for DictionaryReader {
+    fn read(&self, reader: &mut ContentReader, arguments: &[String]) -> Result<XnbValue, XNBError> {
+        let (key, value) = match arguments {
+            [key, value] => (key, value),
+            _ => return Err(XNBError::UnexpectedObject("generic argument")),
+        };
+        let count = reader.input.get_u32_le()?;
+        let mut entries = Vec::with_capacity(capacity(&reader.input, count));
+        for _ in 0..count {
+            entries.push((reader.read_value(key)?, reader.read_value(value)?));
+        }
+        Ok(XnbValue::Map(entries))
+    }
+}
+
+pub struct NullableReader;
+
+impl TypeReader for NullableReader {
+    fn read(&self, reader: &mut ContentReader, arguments: &[String]) -> Result<XnbValue, XNBError> {
+        let value = arguments.first().ok_or(XNBError::UnexpectedObject("generic argument"))?;
+        if reader.input.get_u8()? != 0 {
+            reader.read_value(value)
+        } else {
+            Ok(XnbValue::Null)
+        }
+    }
+}
+
+// Fields of reflected types are not described by the file itself,
+// so only types registered with TypeReaderRegistry::register_reflective_type can be read.
+pub struct ReflectiveReader;
+
+impl TypeReader for ReflectiveReader {
+    fn read(&self, reader: &mut ContentReader, arguments: &[String]) -> Result<XnbValue, XNBError> {
+        let type_name = arguments.first().ok_or(XNBError::UnexpectedObject("generic argument"))?;
+        let fields = reader.registry().reflective_types
+            .get(normalize_reader_name(type_name))
+            .ok_or_else(|| XNBError::UnknownTypeReader(format!("ReflectiveReader`1[[{}]]", type_name)))?
+            .clone();
+
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            values.push(if field.shared_resource {
+                reader.read_shared_resource()?
+            } else {
+                reader.read_value(&field.type_name)?
+            });
+        }
+        Ok(XnbValue::Object { type_name: type_name.clone(), fields: values })
+    }
+}
+
+fn read_texture(reader: &mut ContentReader, alpha: AlphaMode) -> Result<Vec<Sprite>, XNBError> {
+    let (platform, version) = (reader.platform(), reader.version());
+    let input = &mut reader.input;
+    let offset = input.offset();
+    let format = input.get_u32_le()?;
+    let surface_format = SurfaceFormat::read(format, version).ok_or(XNBError::UnexpectedSurfaceFormat { offset, format })?;
+    let width = input.get_u32_le()?;
+    let height = input.get_u32_le()?;
+    let mipmaps = input.get_u32_le()?;
+
+    // XNA 3.1 did not premultiply alpha yet.
+    let premultiplied = version == FormatVersion::Xna40;
+
+    let mut levels = Vec::with_capacity(capacity(input, mipmaps));
+    for level in 0..mipmaps {
+        let offset = input.offset();
+        let size = input.get_u32_le()? as usize;
+        let width = width.checked_shr(level).unwrap_or(0).max(1);
+        let height = height.checked_shr(level).unwrap_or(0).max(1);
+        let expected = surface_format.level_size(width, height)
+            .ok_or(XNBError::UnexpectedSurfaceFormat { offset, format })?;
+        if size != expected {
+            return Err(XNBError::SizeMismatch { offset, expected, actual: size });
+        }
+
+        // Color data becomes the pixels of the sprite without another copy.
+        let mut data = input.read_bytes(size)?;
+        if platform == Platform::Xbox360 {
+            surface::swap_bytes(surface_format, &mut data);
+        }
+        let mut sprite = surface::decode(surface_format, width, height, data)
+            .ok_or(XNBError::UnexpectedSurfaceFormat { offset, format })?;
+
+        if version == FormatVersion::Xna31 && surface_format == SurfaceFormat::Color {
+            surface::swap_red_blue(&mut sprite);
+        }
+        match (alpha, premultiplied) {
+            (AlphaMode::Straight, true) => surface::unpremultiply(&mut sprite),
+            (AlphaMode::Premultiplied, false) => surface::premultiply(&mut sprite),
+            _ => {}
+        }
+        levels.push(sprite);
+    }
+    Ok(levels)
+}
+
+fn read_list<'a, T>(input: &mut XnbInput<'a>, mut read: impl FnMut(&mut XnbInput<'a>) -> Result<T, XNBError>) -> Result<Vec<T>, XNBError> {
+    let count = input.get_u32_le()?;
+    let mut values = Vec::with_capacity(capacity(input, count));
+    for _ in 0..count {
+        values.push(read(input)?);
+    }
+    Ok(values)
+}
+
+// Every element takes at least a byte, so a corrupt count can't reserve more than the data left.
+fn capacity(input: &XnbInput, count: u32) -> usize {
+    (count as usize).min(input.remaining())
+}
+
+fn read_rectangle(input: &mut XnbInput) -> Result<Rectangle, XNBError> {
+    Ok(Rectangle {
+        x: input.get_i32_le()?,
+        y: input.get_i32_le()?,
+        width: input.get_i32_le()?,
+        height: input.get_i32_le()?,
+    })
+}
+
+fn read_vector(input: &mut XnbInput, size: usize) -> Result<XnbValue, XNBError> {
+    let mut values = Vec::with_capacity(size);
+    for _ in 0..size {
+        values.push(input.get_f32_le()?);
+    }
+    Ok(XnbValue::Vector(values))
+}
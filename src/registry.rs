@@ -1,3 +1,5 @@
+use serde::Serialize;
+
 type Id = (u32, &'static str);
 
 macro_rules! index {
@@ -527,4 +529,487 @@ index!(WALLS 302 => [
 "large_bamboo_block"	313
 "amber_stone"	314
 "bamboo_fence"	315
-]);
\ No newline at end of file
+]);
+
+// Terraria ItemID -> Rustaria item
+index!(ITEMS 100 => [
+"acorn"	27
+"adamantite_bar"	391
+"adamantite_ore"	366
+"amethyst"	181
+"ash"	172
+"blue_brick"	134
+"bone"	154
+"boreal_wood"	2503
+"cactus"	276
+"chlorophyte_bar"	1006
+"chlorophyte_ore"	947
+"clay"	133
+"cobalt_bar"	381
+"cobalt_ore"	364
+"cobweb"	150
+"copper_bar"	20
+"copper_brick"	145
+"copper_ore"	12
+"crimstone"	836
+"crimtane_bar"	1257
+"crimtane_ore"	880
+"demonite_bar"	57
+"demonite_ore"	56
+"diamond"	182
+"dirt"	2
+"ebonsand"	370
+"ebonstone"	61
+"ebonwood"	619
+"emerald"	179
+"fallen_star"	75
+"gel"	23
+"glass"	170
+"glowing_mushroom"	183
+"gold_bar"	19
+"gold_brick"	141
+"gold_ore"	13
+"granite"	3086
+"gray_brick"	129
+"green_brick"	137
+"hay"	1727
+"hellstone"	174
+"hellstone_bar"	175
+"ice"	664
+"iron_bar"	22
+"iron_ore"	11
+"lead_bar"	704
+"lead_ore"	700
+"luminite"	3460
+"luminite_bar"	3467
+"marble"	3081
+"meteorite"	116
+"meteorite_bar"	117
+"mud"	176
+"mushroom"	5
+"mythril_bar"	382
+"mythril_ore"	365
+"obsidian"	173
+"obsidian_brick"	192
+"orichalcum_bar"	1191
+"orichalcum_ore"	1105
+"palladium_bar"	1184
+"palladium_ore"	1104
+"palm_wood"	2504
+"pearlsand"	408
+"pearlstone"	409
+"pearlwood"	621
+"pink_brick"	139
+"platinum_bar"	706
+"platinum_ore"	702
+"red_brick"	131
+"rich_mahogany"	620
+"ruby"	178
+"sand"	169
+"sapphire"	177
+"shadewood"	911
+"silt"	424
+"silver_bar"	21
+"silver_brick"	143
+"silver_ore"	14
+"slush"	1103
+"snow"	593
+"soul_of_flight"	575
+"soul_of_fright"	547
+"soul_of_light"	520
+"soul_of_might"	548
+"soul_of_night"	521
+"soul_of_sight"	549
+"spike"	147
+"spooky_wood"	1729
+"stone"	3
+"sunflower"	63
+"tin_bar"	703
+"tin_ore"	699
+"titanium_bar"	1198
+"titanium_ore"	1106
+"topaz"	180
+"torch"	8
+"tungsten_bar"	705
+"tungsten_ore"	701
+"wood"	9
+]);
+
+// Items drawn with DrawAnimationVertical, frames stacked top to bottom.
+pub const ITEM_FRAMES: [(u32, u32); 7] = [
+    (75, 8),
+    (520, 4),
+    (521, 4),
+    (547, 4),
+    (548, 4),
+    (549, 4),
+    (575, 4),
+];
+
+// Town NPCs and slimes, Terraria NPCID -> Rustaria entity
+index!(NPCS 36 => [
+"angler"	369
+"arms_dealer"	19
+"blue_slime"	1
+"clothier"	54
+"corrupt_slime"	81
+"crimslime"	183
+"cyborg"	209
+"demolitionist"	38
+"dryad"	20
+"dungeon_slime"	71
+"dye_trader"	207
+"goblin_tinkerer"	107
+"golfer"	588
+"guide"	22
+"ice_slime"	147
+"illuminant_slime"	138
+"king_slime"	50
+"lava_slime"	59
+"mechanic"	124
+"merchant"	17
+"mother_slime"	16
+"nurse"	18
+"painter"	227
+"party_girl"	208
+"pirate"	229
+"princess"	663
+"santa_claus"	142
+"steampunker"	178
+"stylist"	353
+"tavernkeep"	550
+"tax_collector"	441
+"toxic_sludge"	141
+"truffle"	160
+"witch_doctor"	228
+"wizard"	108
+"zoologist"	633
+]);
+
+// Main.npcFrameCount of the NPCs above, frames stacked top to bottom.
+pub const NPC_FRAMES: [(u32, u32); 36] = [
+    (1, 2),
+    (16, 2),
+    (17, 25),
+    (18, 23),
+    (19, 25),
+    (20, 23),
+    (22, 26),
+    (38, 25),
+    (50, 6),
+    (54, 23),
+    (59, 2),
+    (71, 2),
+    (81, 2),
+    (107, 25),
+    (108, 23),
+    (124, 25),
+    (138, 2),
+    (141, 2),
+    (142, 23),
+    (147, 2),
+    (160, 25),
+    (178, 25),
+    (183, 2),
+    (207, 25),
+    (208, 25),
+    (209, 25),
+    (227, 25),
+    (228, 25),
+    (229, 25),
+    (353, 25),
+    (369, 25),
+    (441, 25),
+    (550, 25),
+    (588, 25),
+    (633, 25),
+    (663, 25),
+];
+
+// Terraria ProjectileID -> Rustaria projectile
+index!(PROJECTILES 30 => [
+"baby_slime"	266
+"ball_of_fire"	15
+"bee"	181
+"bomb"	28
+"bone"	21
+"bullet"	14
+"cursed_arrow"	103
+"cursed_bullet"	104
+"dynamite"	29
+"enchanted_boomerang"	6
+"falling_star"	12
+"fire_arrow"	2
+"flamarang"	19
+"green_laser"	20
+"grenade"	30
+"hellfire_arrow"	41
+"high_velocity_bullet"	242
+"holy_arrow"	91
+"jesters_arrow"	5
+"magic_missile"	16
+"meteor_shot"	36
+"poisoned_knife"	54
+"shuriken"	3
+"spiky_ball"	24
+"throwing_knife"	48
+"unholy_arrow"	4
+"wasp"	189
+"water_bolt"	27
+"water_stream"	22
+"wooden_arrow"	1
+]);
+
+// Main.projFrames of the projectiles above that have more than one frame.
+pub const PROJECTILE_FRAMES: [(u32, u32); 3] = [
+    (181, 3),
+    (189, 3),
+    (266, 6),
+];
+
+/// Frame count of `id` in one of the frame tables, 1 for anything not listed.
+pub fn frame_count(table: &[(u32, u32)], id: u32) -> u32 {
+    table.iter().find(|frames| frames.0 == id).map_or(1, |frames| frames.1)
+}
+
+/// How far a background layer sits behind the world, back to front.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Sky,
+    Far,
+    Middle,
+    Close,
+}
+
+impl Layer {
+    /// How fast the layer moves relative to the camera, 0 stays put and 1 moves with the world.
+    pub fn scroll_factor(self) -> f64 {
+        match self {
+            Layer::Sky => 0.0,
+            Layer::Far => 0.15,
+            Layer::Middle => 0.37,
+            Layer::Close => 0.43,
+        }
+    }
+}
+
+// Terraria background -> biome and layer, the default surface style of each biome.
+pub const BACKGROUNDS: [(u32, &str, Layer); 22] = [
+    (0, "sky", Layer::Sky),
+    (7, "forest", Layer::Far),
+    (8, "forest", Layer::Middle),
+    (9, "forest", Layer::Close),
+    (12, "corruption", Layer::Far),
+    (13, "corruption", Layer::Middle),
+    (14, "corruption", Layer::Close),
+    (15, "jungle", Layer::Far),
+    (16, "jungle", Layer::Middle),
+    (17, "jungle", Layer::Close),
+    (20, "desert", Layer::Middle),
+    (21, "desert", Layer::Close),
+    (28, "ocean", Layer::Close),
+    (29, "hallow", Layer::Far),
+    (30, "hallow", Layer::Middle),
+    (31, "hallow", Layer::Close),
+    (35, "snow", Layer::Far),
+    (36, "snow", Layer::Middle),
+    (37, "snow", Layer::Close),
+    (50, "crimson", Layer::Far),
+    (51, "crimson", Layer::Middle),
+    (52, "crimson", Layer::Close),
+];
+
+/// Where an equip is drawn on the player, accessories name their Acc_<slot> sheet.
+#[derive(Debug, Copy, Clone)]
+pub enum EquipSlot {
+    Head,
+    Body,
+    Legs,
+    Wings,
+    Accessory(&'static str),
+}
+
+// Terraria equip slot -> Rustaria equipment
+pub const EQUIPMENT: [(EquipSlot, u32, &str); 24] = [
+    (EquipSlot::Head, 1, "copper_helmet"),
+    (EquipSlot::Head, 2, "iron_helmet"),
+    (EquipSlot::Head, 3, "silver_helmet"),
+    (EquipSlot::Head, 4, "gold_helmet"),
+    (EquipSlot::Head, 5, "shadow_helmet"),
+    (EquipSlot::Head, 6, "meteor_helmet"),
+    (EquipSlot::Body, 1, "copper_chainmail"),
+    (EquipSlot::Body, 2, "iron_chainmail"),
+    (EquipSlot::Body, 3, "silver_chainmail"),
+    (EquipSlot::Body, 4, "gold_chainmail"),
+    (EquipSlot::Body, 5, "shadow_scalemail"),
+    (EquipSlot::Body, 6, "meteor_suit"),
+    (EquipSlot::Legs, 1, "copper_greaves"),
+    (EquipSlot::Legs, 2, "iron_greaves"),
+    (EquipSlot::Legs, 3, "silver_greaves"),
+    (EquipSlot::Legs, 4, "gold_greaves"),
+    (EquipSlot::Legs, 5, "shadow_greaves"),
+    (EquipSlot::Legs, 6, "meteor_leggings"),
+    (EquipSlot::Wings, 1, "demon_wings"),
+    (EquipSlot::Wings, 2, "angel_wings"),
+    (EquipSlot::Accessory("Shoes"), 1, "rocket_boots"),
+    (EquipSlot::Accessory("Shoes"), 2, "hermes_boots"),
+    (EquipSlot::Accessory("Balloon"), 1, "shiny_red_balloon"),
+    (EquipSlot::Accessory("Back"), 1, "cloak"),
+];
+
+// Wings flap through their own frames instead of following the player's.
+pub const WING_FRAMES: u32 = 4;
+
+// Skin variants of the base player, Player_{variant}_{part}.
+pub const PLAYER_VARIANTS: u32 = 10;
+
+index!(PLAYER_PARTS 13 => [
+"head"	0
+"eye_whites"	1
+"eyes"	2
+"torso"	3
+"undershirt"	4
+"hands"	5
+"shirt"	6
+"arm"	7
+"undershirt_arm"	8
+"hand"	9
+"legs"	10
+"pants"	11
+"shoes"	12
+]);
+
+// Terraria BuffID -> Rustaria buff
+index!(BUFFS 48 => [
+"archery"	16
+"baby_eater"	45
+"baby_penguin"	41
+"battle"	13
+"bleeding"	30
+"broken_armor"	36
+"chilled"	46
+"clairvoyance"	29
+"confused"	31
+"cursed"	23
+"cursed_inferno"	39
+"darkness"	22
+"fairy"	27
+"featherfall"	8
+"frostburn"	44
+"frozen"	47
+"gills"	4
+"gravitation"	18
+"honey"	48
+"horrified"	37
+"hunter"	17
+"invisibility"	10
+"ironskin"	5
+"magic_power"	7
+"mana_regeneration"	6
+"merfolk"	34
+"night_owl"	12
+"obsidian_skin"	1
+"on_fire"	24
+"paladins_shield"	43
+"pet_bunny"	40
+"pet_turtle"	42
+"poisoned"	20
+"potion_sickness"	21
+"regeneration"	2
+"shadow_orb"	19
+"shine"	11
+"silenced"	35
+"slow"	32
+"spelunker"	9
+"swiftness"	3
+"the_tongue"	38
+"thorns"	14
+"tipsy"	25
+"water_walking"	15
+"weak"	33
+"well_fed"	26
+"werewolf"	28
+]);
+
+// Liquid_{n} and LiquidSlope_{n} style -> Rustaria liquid, with the Waterfall_{n} style if it has one.
+pub const LIQUIDS: [(u32, &str, Option<u32>); 13] = [
+    (0, "water", Some(0)),
+    (1, "lava", Some(1)),
+    (2, "corruption_water", None),
+    (3, "jungle_water", None),
+    (4, "hallow_water", None),
+    (5, "snow_water", None),
+    (6, "desert_water", None),
+    (7, "underground_water", None),
+    (8, "cavern_water", None),
+    (9, "blood_moon_water", None),
+    (10, "crimson_water", None),
+    (11, "honey", Some(2)),
+    (14, "shimmer", None),
+];
+
+// Tree style -> trunk sheet (Tiles_5_{n}, None for Tiles_5 itself), Tree_Tops_{n} and Tree_Branches_{n}.
+pub const TREES: [(&str, Option<u32>, u32, u32); 6] = [
+    ("forest", None, 0, 0),
+    ("corruption", Some(0), 1, 1),
+    ("jungle", Some(1), 2, 2),
+    ("hallow", Some(2), 3, 3),
+    ("snow", Some(3), 4, 4),
+    ("crimson", Some(4), 5, 5),
+];
+
+/// A frame important tile made of several tiles, described like Terraria's TileObjectData.
+pub struct Furniture {
+    pub id: u32,
+    pub name: &'static str,
+    /// Width in tiles, the height is the number of coordinate_heights.
+    pub width: u32,
+    /// The tile the furniture is placed by, counted from the top left.
+    pub origin: (u32, u32),
+    /// Drawn width of a tile in pixels, every tile is followed by 2px of padding.
+    pub tile_width: u32,
+    /// Drawn height of each row of tiles, top to bottom. Bottom rows of 18px reach 2px into the tile below.
+    pub coordinate_heights: &'static [u32],
+    pub styles: u32,
+    /// Pixels from the top left of one style to the next.
+    pub style_stride: (u32, u32),
+    /// Animation frames, platforms use them for their connection variants.
+    pub frames: u32,
+    pub frame_stride: (u32, u32),
+}
+
+impl Furniture {
+    /// Height in tiles.
+    pub fn height(&self) -> u32 {
+        self.coordinate_heights.len() as u32
+    }
+}
+
+// Terraria tile -> Rustaria furniture. Style counts are upper bounds, styles past the end of the sheet are skipped.
+pub const FURNITURE: [Furniture; 24] = [
+    Furniture { id: 4, name: "torch", width: 1, origin: (0, 0), tile_width: 20, coordinate_heights: &[20], styles: 24, style_stride: (0, 22), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 10, name: "door_closed", width: 1, origin: (0, 1), tile_width: 16, coordinate_heights: &[16, 16, 16], styles: 40, style_stride: (0, 54), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 11, name: "door_open", width: 2, origin: (0, 1), tile_width: 16, coordinate_heights: &[16, 16, 16], styles: 40, style_stride: (0, 54), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 14, name: "table", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 30, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 15, name: "chair", width: 1, origin: (0, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 30, style_stride: (0, 40), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 16, name: "anvil", width: 2, origin: (0, 0), tile_width: 16, coordinate_heights: &[18], styles: 2, style_stride: (36, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 17, name: "furnace", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 1, style_stride: (0, 0), frames: 12, frame_stride: (0, 38) },
+    Furniture { id: 18, name: "work_bench", width: 2, origin: (0, 0), tile_width: 16, coordinate_heights: &[18], styles: 30, style_stride: (36, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 19, name: "platform", width: 1, origin: (0, 0), tile_width: 16, coordinate_heights: &[16], styles: 30, style_stride: (0, 18), frames: 27, frame_stride: (18, 0) },
+    Furniture { id: 21, name: "chest", width: 2, origin: (0, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 52, style_stride: (36, 0), frames: 3, frame_stride: (0, 38) },
+    Furniture { id: 26, name: "demon_altar", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 2, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 42, name: "lantern", width: 1, origin: (0, 0), tile_width: 16, coordinate_heights: &[16, 16], styles: 20, style_stride: (0, 36), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 77, name: "hellforge", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 1, style_stride: (0, 0), frames: 12, frame_stride: (0, 38) },
+    Furniture { id: 79, name: "bed", width: 4, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 20, style_stride: (0, 38), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 86, name: "loom", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 1, style_stride: (0, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 87, name: "piano", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 20, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 88, name: "dresser", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 20, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 89, name: "bench", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 20, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 90, name: "bathtub", width: 4, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 20, style_stride: (0, 38), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 93, name: "lamp", width: 1, origin: (0, 2), tile_width: 16, coordinate_heights: &[16, 16, 18], styles: 20, style_stride: (0, 54), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 101, name: "bookcase", width: 3, origin: (1, 3), tile_width: 16, coordinate_heights: &[16, 16, 16, 16], styles: 20, style_stride: (54, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 104, name: "grandfather_clock", width: 2, origin: (0, 4), tile_width: 16, coordinate_heights: &[16, 16, 16, 16, 16], styles: 20, style_stride: (36, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 114, name: "tinkerers_workshop", width: 3, origin: (1, 1), tile_width: 16, coordinate_heights: &[16, 18], styles: 1, style_stride: (0, 0), frames: 1, frame_stride: (0, 0) },
+    Furniture { id: 134, name: "mythril_anvil", width: 2, origin: (0, 0), tile_width: 16, coordinate_heights: &[18], styles: 2, style_stride: (36, 0), frames: 1, frame_stride: (0, 0) },
+];
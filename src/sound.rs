@@ -0,0 +1,158 @@
+use std::io::{self, Write};
+
+use crate::xnb::{XNBError, XnbInput};
+
+// WAVEFORMATEX as stored by the SoundEffect content writer.
+#[derive(Clone)]
+pub struct WaveFormat {
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub avg_bytes_per_sec: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    // cbSize and whatever extra data follows it, written back untouched.
+    pub extra: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct Sound {
+    pub format: WaveFormat,
+    pub data: Vec<u8>,
+    /// Loop start and length in samples.
+    pub loop_start: u32,
+    pub loop_length: u32,
+}
+
+impl WaveFormat {
+    pub fn read(input: &mut XnbInput, size: usize) -> Result<WaveFormat, XNBError> {
+        let offset = input.offset();
+        let extra = size.checked_sub(16).ok_or(XNBError::Truncated { offset, needed: 16 })?;
+        Ok(WaveFormat {
+            format_tag: input.get_u16_le()?,
+            channels: input.get_u16_le()?,
+            sample_rate: input.get_u32_le()?,
+            avg_bytes_per_sec: input.get_u32_le()?,
+            block_align: input.get_u16_le()?,
+            bits_per_sample: input.get_u16_le()?,
+            extra: input.read_bytes(extra)?,
+        })
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.format_tag.to_le_bytes())?;
+        out.write_all(&self.channels.to_le_bytes())?;
+        out.write_all(&self.sample_rate.to_le_bytes())?;
+        out.write_all(&self.avg_bytes_per_sec.to_le_bytes())?;
+        out.write_all(&self.block_align.to_le_bytes())?;
+        out.write_all(&self.bits_per_sample.to_le_bytes())?;
+        out.write_all(&self.extra)
+    }
+
+    fn size(&self) -> u32 {
+        16 + self.extra.len() as u32
+    }
+}
+
+impl Sound {
+    pub fn write_wav(&self, out: &mut impl Write) -> io::Result<()> {
+        // Inclusive end of the loop, sounds that never loop get no smpl chunk.
+        let loop_end = self.loop_length.checked_sub(1).and_then(|length| self.loop_start.checked_add(length));
+        let fmt_size = self.format.size();
+        let data_size = self.data.len() as u32;
+        // smpl header + a single loop
+        let smpl_size = 36 + 24;
+
+        let mut riff_size = 4 + (8 + fmt_size) + (8 + data_size + data_size % 2);
+        if loop_end.is_some() {
+            riff_size += 8 + smpl_size;
+        }
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&riff_size.to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+
+        out.write_all(b"fmt ")?;
+        out.write_all(&fmt_size.to_le_bytes())?;
+        self.format.write(out)?;
+
+        out.write_all(b"data")?;
+        out.write_all(&data_size.to_le_bytes())?;
+        out.write_all(&self.data)?;
+        // Chunks are word aligned.
+        if data_size % 2 == 1 {
+            out.write_all(&[0])?;
+        }
+
+        if let Some(loop_end) = loop_end {
+            out.write_all(b"smpl")?;
+            out.write_all(&smpl_size.to_le_bytes())?;
+            for value in [
+                0, // manufacturer
+                0, // product
+                1_000_000_000 / self.format.sample_rate.max(1), // sample period in nanoseconds
+                60, // MIDI unity note
+                0, // MIDI pitch fraction
+                0, // SMPTE format
+                0, // SMPTE offset
+                1, // loop count
+                0, // sampler data
+                // loop
+                0, // cue point id
+                0, // forward loop
+                self.loop_start,
+                loop_end,
+                0, // fraction
+                0, // play count, 0 loops forever
+            ] {
+                out.write_all(&u32::to_le_bytes(value))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sound(loop_start: u32, loop_length: u32) -> Sound {
+        let format = WaveFormat {
+            format_tag: 1,
+            channels: 1,
+            sample_rate: 44100,
+            avg_bytes_per_sec: 88200,
+            block_align: 2,
+            bits_per_sample: 16,
+            extra: Vec::new(),
+        };
+        Sound { format, data: vec![0; 8], loop_start, loop_length }
+    }
+
+    fn has_smpl(sound: &Sound) -> bool {
+        let mut out = Vec::new();
+        sound.write_wav(&mut out).unwrap();
+        out.windows(4).any(|chunk| chunk == b"smpl")
+    }
+
+    #[test]
+    fn no_smpl_without_loop() {
+        assert!(!has_smpl(&sound(0, 0)));
+        assert!(!has_smpl(&sound(2, 0)));
+    }
+
+    #[test]
+    fn no_smpl_when_loop_overflows() {
+        assert!(!has_smpl(&sound(u32::MAX, 2)));
+    }
+
+    #[test]
+    fn smpl_loop_end_is_inclusive() {
+        let mut out = Vec::new();
+        sound(1, 3).write_wav(&mut out).unwrap();
+        // Only the fraction and play count follow the loop end.
+        let end = &out[out.len() - 12..out.len() - 8];
+        assert_eq!(u32::from_le_bytes(end.try_into().unwrap()), 3);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::asset::Sprite;
+use crate::xnb::FormatVersion;
+
+// Microsoft.Xna.Framework.Graphics.SurfaceFormat (XNA 4.0)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    Color,
+    Bgr565,
+    Bgra5551,
+    Bgra4444,
+    Dxt1,
+    Dxt3,
+    Dxt5,
+    NormalizedByte2,
+    NormalizedByte4,
+    Rgba1010102,
+    Rg32,
+    Rgba64,
+    Alpha8,
+    Single,
+    Vector2,
+    Vector4,
+    HalfSingle,
+    HalfVector2,
+    HalfVector4,
+    HdrBlendable,
+}
+
+impl SurfaceFormat {
+    pub fn from_id(id: u32) -> Option<SurfaceFormat> {
+        use SurfaceFormat::*;
+        Some(match id {
+            0 => Color,
+            1 => Bgr565,
+            2 => Bgra5551,
+            3 => Bgra4444,
+            4 => Dxt1,
+            5 => Dxt3,
+            6 => Dxt5,
+            7 => NormalizedByte2,
+            8 => NormalizedByte4,
+            9 => Rgba1010102,
+            10 => Rg32,
+            11 => Rgba64,
+            12 => Alpha8,
+            13 => Single,
+            14 => Vector2,
+            15 => Vector4,
+            16 => HalfSingle,
+            17 => HalfVector2,
+            18 => HalfVector4,
+            19 => HdrBlendable,
+            _ => return None,
+        })
+    }
+
+    /// Reads a surface format id of the given XNB version.
+    pub fn read(id: u32, version: FormatVersion) -> Option<SurfaceFormat> {
+        match version {
+            FormatVersion::Xna31 => SurfaceFormat::from_legacy_id(id),
+            FormatVersion::Xna40 => SurfaceFormat::from_id(id),
+        }
+    }
+
+    // Microsoft.Xna.Framework.Graphics.SurfaceFormat (XNA 3.1), formats without an XNA 4.0 equivalent are left out.
+    pub fn from_legacy_id(id: u32) -> Option<SurfaceFormat> {
+        use SurfaceFormat::*;
+        Some(match id {
+            1 => Color,
+            6 => Rgba1010102,
+            7 => Rg32,
+            8 => Rgba64,
+            9 => Bgr565,
+            10 => Bgra5551,
+            12 => Bgra4444,
+            15 => Alpha8,
+            18 => NormalizedByte2,
+            19 => NormalizedByte4,
+            22 => Single,
+            23 => Vector2,
+            24 => Vector4,
+            25 => HalfSingle,
+            26 => HalfVector2,
+            27 => HalfVector4,
+            28 => Dxt1,
+            30 => Dxt3,
+            32 => Dxt5,
+            _ => return None,
+        })
+    }
+
+    // Size of the values that make up the data, DXT blocks are made of 16 bit values.
+    fn element_size(&self) -> usize {
+        match self {
+            SurfaceFormat::Color => 4,
+            SurfaceFormat::Bgr565 | SurfaceFormat::Bgra5551 | SurfaceFormat::Bgra4444 => 2,
+            SurfaceFormat::Dxt1 | SurfaceFormat::Dxt3 | SurfaceFormat::Dxt5 => 2,
+            _ => 1,
+        }
+    }
+
+    /// Amount of bytes a single mip level of the given size takes up, if the format can be decoded
+    /// and the size fits in memory.
+    pub fn level_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (width, height) = (width as usize, height as usize);
+        let (pixels, size) = match self {
+            SurfaceFormat::Color => (width.checked_mul(height)?, 4),
+            SurfaceFormat::Bgr565 | SurfaceFormat::Bgra5551 | SurfaceFormat::Bgra4444 => (width.checked_mul(height)?, 2),
+            SurfaceFormat::Alpha8 => (width.checked_mul(height)?, 1),
+            // Blocks of 4x4 pixels.
+            SurfaceFormat::Dxt1 => (width.div_ceil(4).checked_mul(height.div_ceil(4))?, 8),
+            SurfaceFormat::Dxt3 | SurfaceFormat::Dxt5 => (width.div_ceil(4).checked_mul(height.div_ceil(4))?, 16),
+            _ => return None,
+        };
+        pixels.checked_mul(size)
+    }
+}
+
+/// How the alpha of decoded textures should be represented.
+/// The XNA content pipeline stores textures with premultiplied alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AlphaMode {
+    Premultiplied,
+    Straight,
+}
+
+/// `None` if the format can't be decoded or `data` doesn't hold exactly one level of it.
+pub fn decode(format: SurfaceFormat, width: u32, height: u32, data: Vec<u8>) -> Option<Sprite> {
+    if format.level_size(width, height)? != data.len() {
+        return None;
+    }
+
+    Some(match format {
+        SurfaceFormat::Color => Sprite::from_raw(width, height, data)?,
+        SurfaceFormat::Bgr565 => unpack(width, height, &data, 2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand(v >> 11, 5), expand(v >> 5, 6), expand(v, 5), 255]
+        })?,
+        SurfaceFormat::Bgra5551 => unpack(width, height, &data, 2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand(v >> 10, 5), expand(v >> 5, 5), expand(v, 5), expand(v >> 15, 1)]
+        })?,
+        SurfaceFormat::Bgra4444 => unpack(width, height, &data, 2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand(v >> 8, 4), expand(v >> 4, 4), expand(v, 4), expand(v >> 12, 4)]
+        })?,
+        SurfaceFormat::Alpha8 => unpack(width, height, &data, 1, |p| [0, 0, 0, p[0]])?,
+        SurfaceFormat::Dxt1 => decode_blocks(width, height, &data, 8, |block, out| {
+            decode_color_block(block, out, true);
+        }),
+        SurfaceFormat::Dxt3 => decode_blocks(width, height, &data, 16, |block, out| {
+            decode_color_block(&block[8..], out, false);
+            decode_explicit_alpha(&block[..8], out);
+        }),
+        SurfaceFormat::Dxt5 => decode_blocks(width, height, &data, 16, |block, out| {
+            decode_color_block(&block[8..], out, false);
+            decode_interpolated_alpha(&block[..8], out);
+        }),
+        _ => return None,
+    })
+}
+
+fn unpack(width: u32, height: u32, data: &[u8], stride: usize, pixel: impl Fn(&[u8]) -> [u8; 4]) -> Option<Sprite> {
+    let raw = data.chunks_exact(stride).flat_map(pixel).collect();
+    Sprite::from_raw(width, height, raw)
+}
+
+// Scales a channel of the given bit depth up to the full 0-255 range.
+fn expand(value: u16, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+    (((value as u32 & max) * 255 + max / 2) / max) as u8
+}
+
+fn decode_blocks(width: u32, height: u32, data: &[u8], block_size: usize, decode_block: impl Fn(&[u8], &mut [[u8; 4]; 16])) -> Sprite {
+    let mut sprite = Sprite::new(width, height);
+    let blocks_wide = width.div_ceil(4);
+
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let mut pixels = [[0u8; 4]; 16];
+        decode_block(block, &mut pixels);
+
+        let block_x = (i as u32 % blocks_wide) * 4;
+        let block_y = (i as u32 / blocks_wide) * 4;
+        for (j, pixel) in pixels.iter().enumerate() {
+            let x = block_x + j as u32 % 4;
+            let y = block_y + j as u32 / 4;
+            // Blocks on the right and bottom edge may hang over the image.
+            if x < width && y < height {
+                sprite.put_pixel(x, y, image::Rgba(*pixel));
+            }
+        }
+    }
+
+    sprite
+}
+
+// BC1 color block. DXT3/5 always use the four color mode, DXT1 switches to
+// three colors + transparent black when color0 <= color1.
+fn decode_color_block(block: &[u8], out: &mut [[u8; 4]; 16], dxt1: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let rgb = |c: u16| [expand(c >> 11, 5) as u32, expand(c >> 5, 6) as u32, expand(c, 5) as u32];
+    let (a, b) = (rgb(c0), rgb(c1));
+    let mix = |wa: u32, wb: u32, d: u32| {
+        [((a[0] * wa + b[0] * wb) / d) as u8, ((a[1] * wa + b[1] * wb) / d) as u8, ((a[2] * wa + b[2] * wb) / d) as u8, 255]
+    };
+
+    let palette = if c0 > c1 || !dxt1 {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0, 0, 0, 0]]
+    };
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 0b11) as usize];
+    }
+}
+
+// BC2 alpha, 4 bits per pixel.
+fn decode_explicit_alpha(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let nibble = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+        pixel[3] = nibble * 17;
+    }
+}
+
+// BC3 alpha, two endpoints and a 3 bit index per pixel.
+fn decode_interpolated_alpha(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    let a0 = block[0] as u32;
+    let a1 = block[1] as u32;
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = ((a0 * (7 - i as u32) + a1 * i as u32) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((a0 * (5 - i as u32) + a1 * i as u32) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut indices = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        indices |= (*byte as u64) << (i * 8);
+    }
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        pixel[3] = palette[((indices >> (i * 3)) & 0b111) as usize];
+    }
+}
+
+/// Xbox 360 content stores texture data big-endian.
+pub fn swap_bytes(format: SurfaceFormat, data: &mut [u8]) {
+    for element in data.chunks_exact_mut(format.element_size()) {
+        element.reverse();
+    }
+}
+
+/// XNA 3.1 stored Color as ARGB, which puts blue first in memory.
+pub fn swap_red_blue(sprite: &mut Sprite) {
+    for pixel in sprite.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+}
+
+// Divides the color channels by alpha again. Fully transparent pixels carry no color and end up black.
+pub fn unpremultiply(sprite: &mut Sprite) {
+    for pixel in sprite.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = (*channel as u32 * 255 + alpha / 2)
+                .checked_div(alpha)
+                .map_or(0, |value| value.min(255) as u8);
+        }
+    }
+}
+
+// Inverse of unpremultiply, for handing straight alpha images back to XNA.
+pub fn premultiply(sprite: &mut Sprite) {
+    for pixel in sprite.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn pixel(value: [u8; 4]) -> Sprite {
+        Sprite::from_pixel(1, 1, Rgba(value))
+    }
+
+    #[test]
+    fn unpremultiply_opaque_is_unchanged() {
+        let mut sprite = pixel([12, 128, 255, 255]);
+        unpremultiply(&mut sprite);
+        assert_eq!(sprite.get_pixel(0, 0).0, [12, 128, 255, 255]);
+    }
+
+    #[test]
+    fn unpremultiply_half_alpha() {
+        let mut sprite = pixel([64, 0, 128, 128]);
+        unpremultiply(&mut sprite);
+        assert_eq!(sprite.get_pixel(0, 0).0, [128, 0, 255, 128]);
+    }
+
+    #[test]
+    fn unpremultiply_transparent_is_black() {
+        let mut sprite = pixel([40, 50, 60, 0]);
+        unpremultiply(&mut sprite);
+        assert_eq!(sprite.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn premultiply_round_trip() {
+        for alpha in 0..=255u8 {
+            for channel in 0..=alpha {
+                let mut sprite = pixel([channel, alpha / 2, 0, alpha]);
+                unpremultiply(&mut sprite);
+                premultiply(&mut sprite);
+
+                let [red, green, blue, result_alpha] = sprite.get_pixel(0, 0).0;
+                assert_eq!(result_alpha, alpha);
+                assert!(red.abs_diff(channel) <= 1, "{} became {} at alpha {}", channel, red, alpha);
+                assert!(green.abs_diff(alpha / 2) <= 1);
+                assert_eq!(blue, 0);
+            }
+        }
+    }
+
+    // A BC1 block with the endpoints given as 565 values, pixel i gets indices[i].
+    fn color_block(c0: u16, c1: u16, indices: &[u32]) -> Vec<u8> {
+        let bits = indices.iter().enumerate().fold(0u32, |bits, (i, index)| bits | index << (i * 2));
+        [c0.to_le_bytes(), c1.to_le_bytes()].concat().into_iter().chain(bits.to_le_bytes()).collect()
+    }
+
+    fn pixels(sprite: &Sprite, count: usize) -> Vec<[u8; 4]> {
+        sprite.pixels().take(count).map(|pixel| pixel.0).collect()
+    }
+
+    #[test]
+    fn level_size_overflow() {
+        assert_eq!(SurfaceFormat::Color.level_size(u32::MAX, u32::MAX), None);
+        assert_eq!(SurfaceFormat::Dxt5.level_size(5, 6), Some(64));
+    }
+
+    #[test]
+    fn decode_packed_formats() {
+        let bgr565 = decode(SurfaceFormat::Bgr565, 2, 1, vec![0x00, 0xF8, 0xE0, 0x07]).unwrap();
+        assert_eq!(pixels(&bgr565, 2), [[255, 0, 0, 255], [0, 255, 0, 255]]);
+
+        let bgra5551 = decode(SurfaceFormat::Bgra5551, 2, 1, vec![0x1F, 0x80, 0x00, 0x7C]).unwrap();
+        assert_eq!(pixels(&bgra5551, 2), [[0, 0, 255, 255], [255, 0, 0, 0]]);
+
+        let bgra4444 = decode(SurfaceFormat::Bgra4444, 1, 1, vec![0x34, 0x12]).unwrap();
+        assert_eq!(pixels(&bgra4444, 1), [[34, 51, 68, 17]]);
+    }
+
+    #[test]
+    fn decode_dxt1_four_colors() {
+        // Red before blue, c0 > c1.
+        let block = color_block(0xF800, 0x001F, &[0, 1, 2, 3]);
+        let sprite = decode(SurfaceFormat::Dxt1, 4, 4, block).unwrap();
+        assert_eq!(pixels(&sprite, 4), [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]);
+    }
+
+    #[test]
+    fn decode_dxt1_three_colors_and_transparent() {
+        // Blue before red, c0 <= c1.
+        let block = color_block(0x001F, 0xF800, &[0, 1, 2, 3]);
+        let sprite = decode(SurfaceFormat::Dxt1, 4, 4, block).unwrap();
+        assert_eq!(pixels(&sprite, 4), [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn decode_dxt3_nibble_order() {
+        // Low nibble first, the color block stays in four color mode even with c0 <= c1.
+        let mut block = vec![0x21, 0xF3, 0, 0, 0, 0, 0, 0];
+        block.extend(color_block(0x001F, 0xF800, &[0, 0, 0, 3]));
+        let sprite = decode(SurfaceFormat::Dxt3, 4, 4, block).unwrap();
+        assert_eq!(pixels(&sprite, 4), [[0, 0, 255, 17], [0, 0, 255, 34], [0, 0, 255, 51], [170, 0, 85, 255]]);
+    }
+
+    #[test]
+    fn decode_dxt5_alpha_palettes() {
+        // Indices 2, 7 and 6 for the first three pixels.
+        let indices = [0xBA, 0x01, 0, 0, 0, 0];
+        let color = color_block(0xFFFF, 0xFFFF, &[]);
+
+        // a0 > a1 interpolates six values.
+        let block = [&[255, 0][..], &indices, &color].concat();
+        let sprite = decode(SurfaceFormat::Dxt5, 4, 4, block).unwrap();
+        assert_eq!(pixels(&sprite, 3).iter().map(|pixel| pixel[3]).collect::<Vec<_>>(), [218, 36, 72]);
+
+        // a0 <= a1 interpolates four values, followed by 0 and 255.
+        let block = [&[0, 255][..], &indices, &color].concat();
+        let sprite = decode(SurfaceFormat::Dxt5, 4, 4, block).unwrap();
+        assert_eq!(pixels(&sprite, 3).iter().map(|pixel| pixel[3]).collect::<Vec<_>>(), [51, 255, 0]);
+    }
+
+    #[test]
+    fn decode_blocks_over_the_edge() {
+        // 5x6 takes 2x2 blocks, each a solid color.
+        let colors = [0xF800, 0x07E0, 0x001F, 0xFFFF];
+        let data = colors.iter().flat_map(|&color| color_block(color, color, &[])).collect();
+        let sprite = decode(SurfaceFormat::Dxt1, 5, 6, data).unwrap();
+
+        assert_eq!(sprite.dimensions(), (5, 6));
+        assert_eq!(sprite.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert_eq!(sprite.get_pixel(4, 0).0, [0, 255, 0, 255]);
+        assert_eq!(sprite.get_pixel(0, 5).0, [0, 0, 255, 255]);
+        assert_eq!(sprite.get_pixel(4, 5).0, [255, 255, 255, 255]);
+    }
+}
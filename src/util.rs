@@ -1,5 +1,5 @@
 use indicatif::ProgressBar;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct TerrariaFile {
     pub name: String,
@@ -7,7 +7,7 @@ pub struct TerrariaFile {
 
 pub fn seek_files<'a>(
     dir: &Path,
-    progress_bar: &'a mut ProgressBar,
+    progress_bar: &'a ProgressBar,
     message: &'static str,
 ) -> FileIterator<'a> {
     let dir = std::fs::read_dir(dir).unwrap();
@@ -41,3 +41,23 @@ impl<'a> Iterator for FileIterator<'a> {
         self.files.pop()
     }
 }
+
+/// Every .xnb file at `path`, which is either a file or a directory searched recursively.
+pub fn find_xnb_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_xnb_files(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "xnb") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
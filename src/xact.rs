@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use oxidizer::binary::{SliceReader, Truncated};
+use oxidizer::sound::{Sound, WaveFormat};
+
+// WAVEBANKDATA flags
+const BANK_ENTRY_NAMES: u32 = 0x0001_0000;
+const BANK_COMPACT: u32 = 0x0002_0000;
+
+// Segments of the wave bank, see WAVEBANKHEADER.
+const SEGMENT_BANK_DATA: usize = 0;
+const SEGMENT_ENTRY_METADATA: usize = 1;
+const SEGMENT_SEEK_TABLES: usize = 2;
+const SEGMENT_ENTRY_NAMES: usize = 3;
+const SEGMENT_WAVE_DATA: usize = 4;
+
+// Wave banks older than XACT 3 have no header version and a different layout.
+const MIN_WAVE_BANK_VERSION: u32 = 42;
+
+const ADPCM_BLOCK_ALIGN_OFFSET: u32 = 22;
+const WMA_BLOCK_ALIGN: [u32; 17] = [929, 1487, 1280, 2230, 8917, 8192, 4459, 5945, 2304, 1536, 1485, 1008, 2731, 4096, 6827, 5462, 1280];
+const WMA_AVG_BYTES_PER_SEC: [u32; 7] = [12000, 24000, 4000, 6000, 8000, 20000, 2500];
+
+const ADPCM_ADAPTATION: [i32; 16] = [230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230];
+const ADPCM_COEFFICIENTS: [(i32, i32); 7] = [(256, 0), (512, -256), (0, 0), (192, 64), (240, 0), (460, -208), (392, -232)];
+
+#[derive(Error, Debug)]
+pub enum XactError {
+    #[error("Not an XACT wave bank.")]
+    NotAWaveBank,
+    #[error("Not an XACT sound bank.")]
+    NotASoundBank,
+    #[error("XACT wave bank version {0} is not supported.")]
+    UnsupportedVersion(u32),
+    #[error("Needed {needed} more bytes at offset {offset}, but the data ended.")]
+    Truncated { offset: usize, needed: usize },
+
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+}
+
+impl From<Truncated> for XactError {
+    fn from(Truncated { offset, needed }: Truncated) -> XactError {
+        XactError::Truncated { offset, needed }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum Codec {
+    Pcm,
+    /// Xbox 360 only.
+    Xma,
+    /// Microsoft ADPCM.
+    Adpcm,
+    /// xWMA, WMA Pro when `bits_per_sample` is set.
+    Wma,
+}
+
+// WAVEBANKMINIWAVEFORMAT, a WAVEFORMATEX packed into 32 bits.
+#[derive(Debug, Copy, Clone)]
+pub struct MiniFormat {
+    pub codec: Codec,
+    pub channels: u16,
+    pub sample_rate: u32,
+    block_align: u32,
+    // 16 bit for PCM, WMA Pro for xWMA.
+    bits_per_sample: bool,
+}
+
+impl MiniFormat {
+    fn parse(value: u32) -> MiniFormat {
+        MiniFormat {
+            codec: match value & 0x3 {
+                0 => Codec::Pcm,
+                1 => Codec::Xma,
+                2 => Codec::Adpcm,
+                _ => Codec::Wma,
+            },
+            channels: ((value >> 2) & 0x7) as u16,
+            sample_rate: (value >> 5) & 0x3FFFF,
+            block_align: (value >> 23) & 0xFF,
+            bits_per_sample: value >> 31 != 0,
+        }
+    }
+
+    pub fn block_align(&self) -> u32 {
+        match self.codec {
+            Codec::Pcm | Codec::Xma => self.block_align,
+            Codec::Adpcm => (self.block_align + ADPCM_BLOCK_ALIGN_OFFSET) * self.channels as u32,
+            Codec::Wma => WMA_BLOCK_ALIGN.get(self.block_align as usize & 0x1F).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        match self.codec {
+            Codec::Pcm if !self.bits_per_sample => 8,
+            Codec::Adpcm => 4,
+            _ => 16,
+        }
+    }
+
+    fn avg_bytes_per_sec(&self) -> u32 {
+        match self.codec {
+            Codec::Wma => WMA_AVG_BYTES_PER_SEC.get(self.block_align as usize >> 5).copied().unwrap_or(0),
+            _ => self.sample_rate * self.block_align(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WaveEntry {
+    /// From the entry name segment, which most banks leave out.
+    pub name: Option<String>,
+    pub format: MiniFormat,
+    /// Length in samples.
+    pub duration: u32,
+    // Relative to the wave data segment.
+    offset: u32,
+    length: u32,
+    pub loop_start: u32,
+    pub loop_length: u32,
+    // Decoded packet cumulative data sizes of xWMA entries.
+    seek_table: Vec<u32>,
+}
+
+/// An XACT wave bank. Only the entry metadata is read up front, the waves are read on demand.
+pub struct WaveBank {
+    file: File,
+    pub name: String,
+    pub entries: Vec<WaveEntry>,
+    // Xbox 360 banks are big-endian.
+    big_endian: bool,
+    wave_data_offset: u64,
+}
+
+/// A wave converted into something common tools understand.
+pub enum Wave {
+    /// PCM and ADPCM entries, decoded to PCM.
+    Pcm(Sound),
+    /// xWMA entries in a RIFF container, which ffmpeg converts.
+    Xwma(Vec<u8>),
+    /// XMA entries as stored, only the Xbox 360 SDK reads these.
+    Raw(Vec<u8>),
+}
+
+impl Wave {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Wave::Pcm(_) => "wav",
+            Wave::Xwma(_) => "xwma",
+            Wave::Raw(_) => "xma",
+        }
+    }
+}
+
+impl WaveBank {
+    pub fn open(path: &Path) -> Result<WaveBank, XactError> {
+        let mut file = File::open(path)?;
+        let mut header = [0; 52];
+        file.read_exact(&mut header).map_err(|_| XactError::NotAWaveBank)?;
+
+        let big_endian = match &header[..4] {
+            b"WBND" => false,
+            b"DNBW" => true,
+            _ => return Err(XactError::NotAWaveBank),
+        };
+        let mut reader = SliceReader { data: &header, offset: 4, big_endian };
+        let version = reader.u32()?;
+        let _header_version = reader.u32()?;
+        if version < MIN_WAVE_BANK_VERSION {
+            return Err(XactError::UnsupportedVersion(version));
+        }
+
+        let mut segments = [(0, 0); 5];
+        for segment in &mut segments {
+            *segment = (reader.u32()? as u64, reader.u32()?);
+        }
+
+        let bank_data = read_segment(&mut file, segments[SEGMENT_BANK_DATA])?;
+        let mut reader = SliceReader { data: &bank_data, offset: 0, big_endian };
+        let flags = reader.u32()?;
+        let entry_count = reader.u32()?;
+        let name = reader.string(64)?;
+        let metadata_size = reader.u32()?;
+        let name_size = reader.u32()?;
+        let alignment = reader.u32()?;
+        let compact_format = MiniFormat::parse(reader.u32()?);
+
+        let metadata = read_segment(&mut file, segments[SEGMENT_ENTRY_METADATA])?;
+        let names = if flags & BANK_ENTRY_NAMES != 0 {
+            read_segment(&mut file, segments[SEGMENT_ENTRY_NAMES])?
+        } else {
+            Vec::new()
+        };
+        let seek_tables = read_segment(&mut file, segments[SEGMENT_SEEK_TABLES])?;
+
+        let wave_data_length = segments[SEGMENT_WAVE_DATA].1;
+        let mut entries = Vec::with_capacity((entry_count as usize).min(metadata.len()));
+        let mut reader = SliceReader { data: &metadata, offset: 0, big_endian };
+        for i in 0..entry_count as usize {
+            let mut entry = if flags & BANK_COMPACT != 0 {
+                reader.seek(i * 4);
+                let value = reader.u32()?;
+                WaveEntry {
+                    name: None,
+                    format: compact_format,
+                    duration: 0,
+                    offset: (value & 0x1F_FFFF).saturating_mul(alignment),
+                    // Length deviation for now, the length follows from the offset of the next entry.
+                    length: value >> 21,
+                    loop_start: 0,
+                    loop_length: 0,
+                    seek_table: Vec::new(),
+                }
+            } else {
+                reader.seek(i * metadata_size as usize);
+                read_entry(&mut reader, metadata_size, compact_format)?
+            };
+
+            if name_size > 0 && !names.is_empty() {
+                let mut reader = SliceReader { data: &names, offset: i * name_size as usize, big_endian };
+                entry.name = Some(reader.string(name_size as usize)?).filter(|name| !name.is_empty());
+            }
+            if entry.format.codec == Codec::Wma && !seek_tables.is_empty() {
+                entry.seek_table = read_seek_table(&seek_tables, i, entry_count as usize, big_endian)?;
+            }
+            entries.push(entry);
+        }
+
+        if flags & BANK_COMPACT != 0 {
+            let ends: Vec<u32> = entries.iter().skip(1).map(|entry| entry.offset).chain([wave_data_length]).collect();
+            for (entry, end) in entries.iter_mut().zip(ends) {
+                entry.length = end.saturating_sub(entry.offset).saturating_sub(entry.length);
+            }
+        }
+
+        Ok(WaveBank {
+            file,
+            name,
+            entries,
+            big_endian,
+            wave_data_offset: segments[SEGMENT_WAVE_DATA].0,
+        })
+    }
+
+    pub fn read_wave(&mut self, index: usize) -> Result<Wave, XactError> {
+        let entry = &self.entries[index];
+        let mut data = read_segment(&mut self.file, (self.wave_data_offset + entry.offset as u64, entry.length))?;
+
+        let format = entry.format;
+        let channels = format.channels.max(1);
+        Ok(match format.codec {
+            Codec::Pcm | Codec::Adpcm => {
+                let (data, bits_per_sample) = if format.codec == Codec::Adpcm {
+                    let mut samples = decode_adpcm(&data, channels as usize, format.block_align() as usize);
+                    // The last block is padded. Compact banks don't know the duration.
+                    if entry.duration > 0 {
+                        samples.truncate(entry.duration as usize * channels as usize);
+                    }
+                    (samples.iter().flat_map(|sample| sample.to_le_bytes()).collect(), 16)
+                } else {
+                    if self.big_endian && format.bits_per_sample() == 16 {
+                        data.chunks_exact_mut(2).for_each(|sample| sample.swap(0, 1));
+                    }
+                    (data, format.bits_per_sample())
+                };
+
+                let block_align = channels * bits_per_sample / 8;
+                Wave::Pcm(Sound {
+                    format: WaveFormat {
+                        format_tag: 1,
+                        channels,
+                        sample_rate: format.sample_rate,
+                        avg_bytes_per_sec: format.sample_rate * block_align as u32,
+                        block_align,
+                        bits_per_sample,
+                        extra: Vec::new(),
+                    },
+                    data,
+                    loop_start: entry.loop_start,
+                    loop_length: entry.loop_length,
+                })
+            }
+            Codec::Wma => Wave::Xwma(write_xwma(entry, &data)?),
+            Codec::Xma => Wave::Raw(data),
+        })
+    }
+}
+
+fn read_segment(file: &mut File, (offset, length): (u64, u32)) -> Result<Vec<u8>, XactError> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(length as u64).read_to_end(&mut data)?;
+    if data.len() < length as usize {
+        return Err(XactError::Truncated { offset: offset as usize + data.len(), needed: length as usize - data.len() });
+    }
+    Ok(data)
+}
+
+// WAVEBANKENTRY, banks may leave out the fields at the end.
+fn read_entry(reader: &mut SliceReader, size: u32, compact_format: MiniFormat) -> Result<WaveEntry, XactError> {
+    let mut field = |index: u32| if size >= (index + 1) * 4 { reader.u32().map(Some) } else { Ok(None) };
+    let flags_and_duration = field(0)?.unwrap_or(0);
+    let format = field(1)?.map_or(compact_format, MiniFormat::parse);
+    let offset = field(2)?.unwrap_or(0);
+    let length = field(3)?.unwrap_or(0);
+    let loop_start = field(4)?.unwrap_or(0);
+    let loop_length = field(5)?.unwrap_or(0);
+
+    Ok(WaveEntry {
+        name: None,
+        format,
+        duration: flags_and_duration >> 4,
+        offset,
+        length,
+        loop_start,
+        loop_length,
+        seek_table: Vec::new(),
+    })
+}
+
+// The segment starts with the offset of every table, each table is prefixed by its length.
+fn read_seek_table(data: &[u8], index: usize, entry_count: usize, big_endian: bool) -> Result<Vec<u32>, XactError> {
+    let mut reader = SliceReader { data, offset: index * 4, big_endian };
+    let offset = reader.u32()? as usize;
+    reader.seek(entry_count * 4 + offset);
+    let count = reader.u32()?;
+    Ok((0..count).map(|_| reader.u32()).collect::<Result<_, _>>()?)
+}
+
+fn write_xwma(entry: &WaveEntry, data: &[u8]) -> Result<Vec<u8>, XactError> {
+    let format = entry.format;
+    let format_tag: u16 = if format.bits_per_sample { 0x162 } else { 0x161 };
+    let dpds_size = entry.seek_table.len() as u32 * 4;
+    let riff_size = 4 + (8 + 18) + (8 + dpds_size) + (8 + data.len() as u32);
+
+    let mut out = Vec::new();
+    out.write_all(b"RIFF")?;
+    out.write_all(&riff_size.to_le_bytes())?;
+    out.write_all(b"XWMA")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&18u32.to_le_bytes())?;
+    out.write_all(&format_tag.to_le_bytes())?;
+    out.write_all(&format.channels.to_le_bytes())?;
+    out.write_all(&format.sample_rate.to_le_bytes())?;
+    out.write_all(&format.avg_bytes_per_sec().to_le_bytes())?;
+    out.write_all(&(format.block_align() as u16).to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?;
+
+    out.write_all(b"dpds")?;
+    out.write_all(&dpds_size.to_le_bytes())?;
+    for value in &entry.seek_table {
+        out.write_all(&value.to_le_bytes())?;
+    }
+
+    out.write_all(b"data")?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)?;
+    Ok(out)
+}
+
+// Microsoft ADPCM. Every block starts with a predictor, delta and two samples for each channel,
+// followed by 4 bit samples alternating between the channels.
+fn decode_adpcm(data: &[u8], channels: usize, block_align: usize) -> Vec<i16> {
+    struct Channel {
+        coefficients: (i32, i32),
+        delta: i32,
+        sample1: i32,
+        sample2: i32,
+    }
+
+    let mut out = Vec::new();
+    for block in data.chunks(block_align.max(7 * channels)) {
+        if block.len() < 7 * channels {
+            break;
+        }
+
+        let read = |i: usize| i16::from_le_bytes([block[i], block[i + 1]]) as i32;
+        let mut state: Vec<Channel> = (0..channels)
+            .map(|channel| Channel {
+                coefficients: ADPCM_COEFFICIENTS[(block[channel] as usize).min(ADPCM_COEFFICIENTS.len() - 1)],
+                delta: read(channels + channel * 2),
+                sample1: read(channels * 3 + channel * 2),
+                sample2: read(channels * 5 + channel * 2),
+            })
+            .collect();
+
+        out.extend(state.iter().map(|channel| channel.sample2 as i16));
+        out.extend(state.iter().map(|channel| channel.sample1 as i16));
+
+        let nibbles = block[channels * 7..].iter().flat_map(|byte| [byte >> 4, byte & 0xF]);
+        for (i, nibble) in nibbles.enumerate() {
+            let channel = &mut state[i % channels];
+            let signed = ((nibble as i8) << 4 >> 4) as i32;
+            let predicted = (channel.sample1 * channel.coefficients.0 + channel.sample2 * channel.coefficients.1) >> 8;
+            let sample = (predicted + signed * channel.delta).clamp(i16::MIN as i32, i16::MAX as i32);
+
+            channel.sample2 = channel.sample1;
+            channel.sample1 = sample;
+            // Corrupt data could otherwise overflow it.
+            channel.delta = ((ADPCM_ADAPTATION[nibble as usize] * channel.delta) >> 8).clamp(16, i32::MAX / 768);
+            out.push(sample as i16);
+        }
+    }
+
+    out
+}
+
+/// The cues of an XACT sound bank, only as far as needed to name the waves they play.
+pub struct SoundBank {
+    /// Names of the wave banks the cues refer to by index.
+    pub wave_banks: Vec<String>,
+    pub cues: Vec<Cue>,
+}
+
+pub struct Cue {
+    pub name: String,
+    /// Wave bank and track index of every wave the cue can play.
+    pub waves: Vec<(u8, u16)>,
+}
+
+impl SoundBank {
+    pub fn open(path: &Path) -> Result<SoundBank, XactError> {
+        let data = std::fs::read(path)?;
+        let big_endian = match data.get(..4) {
+            Some(b"SDBK") => false,
+            Some(b"KBDS") => true,
+            _ => return Err(XactError::NotASoundBank),
+        };
+
+        let mut reader = SliceReader { data: &data, offset: 4, big_endian };
+        let _tool_version = reader.u16()?;
+        let _format_version = reader.u16()?;
+        let _crc = reader.u16()?;
+        let _last_modified = (reader.u32()?, reader.u32()?);
+        let _platform = reader.u8()?;
+        let simple_cue_count = reader.u16()? as usize;
+        let complex_cue_count = reader.u16()? as usize;
+        reader.u16()?;
+        let _total_cue_count = reader.u16()?;
+        let wave_bank_count = reader.u8()? as usize;
+        let _sound_count = reader.u16()?;
+        let cue_names_length = reader.u16()? as usize;
+        reader.u16()?;
+        let simple_cues_offset = reader.u32()? as usize;
+        let complex_cues_offset = reader.u32()? as usize;
+        let cue_names_offset = reader.u32()? as usize;
+        reader.u32()?;
+        let _variation_tables_offset = reader.u32()?;
+        reader.u32()?;
+        let wave_bank_names_offset = reader.u32()? as usize;
+
+        reader.seek(wave_bank_names_offset);
+        let wave_banks = (0..wave_bank_count).map(|_| reader.string(64)).collect::<Result<_, _>>()?;
+
+        // Null separated, simple cues first.
+        let mut names = Vec::new();
+        if cue_names_offset != u32::MAX as usize {
+            reader.seek(cue_names_offset);
+            let table = reader.bytes(cue_names_length)?;
+            names = table.split(|&byte| byte == 0).map(|name| String::from_utf8_lossy(name).into_owned()).collect();
+        }
+        let name = |index: usize| names.get(index).cloned().unwrap_or_else(|| format!("cue_{}", index));
+
+        let mut cues = Vec::with_capacity(simple_cue_count + complex_cue_count);
+        for i in 0..simple_cue_count {
+            reader.seek(simple_cues_offset + i * 5);
+            let _flags = reader.u8()?;
+            let sound_offset = reader.u32()? as usize;
+            cues.push(Cue { name: name(i), waves: read_sound(&data, sound_offset, big_endian)? });
+        }
+
+        for i in 0..complex_cue_count {
+            reader.seek(complex_cues_offset + i * 15);
+            let flags = reader.u8()?;
+            let offset = reader.u32()? as usize;
+            let waves = if flags & 0x04 != 0 {
+                read_sound(&data, offset, big_endian)?
+            } else {
+                read_variation_table(&data, offset, big_endian)?
+            };
+            cues.push(Cue { name: name(simple_cue_count + i), waves });
+        }
+
+        Ok(SoundBank { wave_banks, cues })
+    }
+
+    /// Names for the tracks of the given wave bank, cues playing several waves number them.
+    pub fn track_names(&self, wave_bank: &str) -> HashMap<u16, String> {
+        let mut names = HashMap::new();
+        for cue in &self.cues {
+            let waves: Vec<u16> = cue.waves.iter()
+                .filter(|(bank, _)| self.wave_banks.len() == 1 || self.wave_banks.get(*bank as usize).is_some_and(|name| name == wave_bank))
+                .map(|(_, track)| *track)
+                .collect();
+
+            for (i, track) in waves.iter().enumerate() {
+                let name = if waves.len() == 1 { cue.name.clone() } else { format!("{}_{}", cue.name, i) };
+                names.entry(*track).or_insert(name);
+            }
+        }
+        names
+    }
+}
+
+fn read_sound(data: &[u8], offset: usize, big_endian: bool) -> Result<Vec<(u8, u16)>, XactError> {
+    let mut reader = SliceReader { data, offset, big_endian };
+    let flags = reader.u8()?;
+    let _category = reader.u16()?;
+    let _volume = reader.u8()?;
+    let _pitch = reader.u16()?;
+    let _priority = reader.u8()?;
+    reader.u16()?;
+
+    if flags & 0x01 == 0 {
+        let track = reader.u16()?;
+        let wave_bank = reader.u8()?;
+        return Ok(vec![(wave_bank, track)]);
+    }
+
+    let clip_count = reader.u8()?;
+    // RPC and DSP presets are skipped by their length.
+    for present in [flags & 0x0E != 0, flags & 0x10 != 0] {
+        if present {
+            let start = reader.offset;
+            let length = reader.u16()? as usize;
+            reader.seek(start + length);
+        }
+    }
+
+    let mut waves = Vec::new();
+    for _ in 0..clip_count {
+        let _volume = reader.u8()?;
+        let clip_offset = reader.u32()? as usize;
+        reader.u32()?;
+
+        // Only the play wave events at the start of the clip, other events vary in size.
+        let mut clip = SliceReader { data, offset: clip_offset, big_endian };
+        for _ in 0..clip.u8()? {
+            let event = clip.u32()?;
+            let _random_offset = clip.u16()?;
+            if event & 0x1F != 1 {
+                break;
+            }
+            clip.u8()?;
+            let _flags = clip.u8()?;
+            let track = clip.u16()?;
+            let wave_bank = clip.u8()?;
+            let _loop_count = clip.u8()?;
+            let _pan = (clip.u16()?, clip.u16()?);
+            waves.push((wave_bank, track));
+        }
+    }
+    Ok(waves)
+}
+
+fn read_variation_table(data: &[u8], offset: usize, big_endian: bool) -> Result<Vec<(u8, u16)>, XactError> {
+    let mut reader = SliceReader { data, offset, big_endian };
+    let count = reader.u16()?;
+    let flags = reader.u16()?;
+    reader.u8()?;
+    reader.u16()?;
+    reader.u8()?;
+
+    let mut waves = Vec::new();
+    for _ in 0..count {
+        match (flags >> 3) & 0x7 {
+            // Waves with and without weights.
+            kind @ (0 | 4) => {
+                let track = reader.u16()?;
+                let wave_bank = reader.u8()?;
+                if kind == 0 {
+                    reader.u16()?;
+                }
+                waves.push((wave_bank, track));
+            }
+            // Sounds with byte or float weights.
+            kind @ (1 | 3) => {
+                let sound_offset = reader.u32()? as usize;
+                reader.seek(reader.offset + if kind == 1 { 2 } else { 12 });
+                waves.extend(read_sound(data, sound_offset, big_endian)?);
+            }
+            _ => break,
+        }
+    }
+    Ok(waves)
+}
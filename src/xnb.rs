@@ -1,168 +1,785 @@
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{self, BufReader, Cursor, Read, Write};
 use std::path::PathBuf;
 
 use crate::asset::Sprite;
-use bytes::{Buf, Bytes};
-use image::RgbaImage;
+use crate::binary::Truncated;
+use crate::effect::Effect;
+use crate::font::Font;
+use crate::reader::{normalize_reader_name, parse_type_name, TypeReaderRegistry};
+use crate::sound::Sound;
+use crate::surface::{self, AlphaMode, SurfaceFormat};
+use bytes::BufMut;
 use lzxd::{Lzxd, WindowSize};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 use thiserror::Error;
 
 const HEADER_SIZE: i32 = 14;
+// Uncompressed files leave out the decompressed size.
+const UNCOMPRESSED_HEADER_SIZE: i32 = 10;
+const FLAG_HIDEF: u8 = 0x01;
+const FLAG_LZ4: u8 = 0x40;
+const FLAG_LZX: u8 = 0x80;
+// Uncompressed size of every LZX frame except the last one.
+const LZX_FRAME_SIZE: usize = 0x8000;
+// Keeps corrupt files from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+const TEXTURE_2D_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader, Microsoft.Xna.Framework.Graphics, Version=4.0.0.0, Culture=neutral, PublicKeyToken=842cf8be1de50553";
 
 pub enum XNBFile {
-    Texture(Sprite),
+    /// Every mip level of the texture, starting with the full size image.
+    Texture(Vec<Sprite>),
+    Sound(Sound),
+    Font(Font),
+    Effect(Effect),
+    /// Any other content the registered type readers understand.
+    Value(XnbValue),
     Unknown,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum Compression {
+    Lzx,
+    Lz4,
+}
+
+/// The platform the content was built for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum Platform {
+    Windows,
+    /// Zune in XNA 3.1.
+    WindowsPhone,
+    Xbox360,
+    // MonoGame
+    Ios,
+    Android,
+    DesktopGl,
+    MacOs,
+    WindowsStore,
+}
+
+impl Platform {
+    pub fn from_id(id: u8) -> Option<Platform> {
+        use Platform::*;
+        Some(match id {
+            b'w' => Windows,
+            b'm' => WindowsPhone,
+            b'x' => Xbox360,
+            b'i' => Ios,
+            b'a' => Android,
+            b'd' => DesktopGl,
+            b'X' => MacOs,
+            b'W' => WindowsStore,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum FormatVersion {
+    /// Version 4, used by Terraria 1.1 and older.
+    Xna31,
+    /// Version 5, also written by MonoGame.
+    Xna40,
+}
+
+impl FormatVersion {
+    pub fn from_id(id: u8) -> Option<FormatVersion> {
+        match id {
+            4 => Some(FormatVersion::Xna31),
+            5 => Some(FormatVersion::Xna40),
+            _ => None,
+        }
+    }
+}
+
+/// Everything about an XNB file that can be told without decoding its content.
+#[derive(Serialize)]
+pub struct XnbHeader {
+    pub platform: Platform,
+    pub version: FormatVersion,
+    /// Always false for XNA 3.1, which had no profiles.
+    pub hidef: bool,
+    pub compression: Option<Compression>,
+    /// Size of the whole file, including the header.
+    pub file_size: i32,
+    /// Size of the content after the header once decompressed.
+    pub decompressed_size: i32,
+    pub type_readers: Vec<TypeReaderInfo>,
+    pub shared_resources: i32,
+    /// Set when the primary asset is a Texture2D.
+    pub texture: Option<TextureInfo>,
+}
+
+#[derive(Serialize)]
+pub struct TypeReaderInfo {
+    pub name: String,
+    pub version: i32,
+}
+
+#[derive(Serialize)]
+pub struct TextureInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+}
+
+/// An object read by one of the type readers.
+#[derive(Clone)]
+pub enum XnbValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Char(char),
+    String(String),
+    Vector(Vec<f32>),
+    Rect(Rectangle),
+    Point([i32; 2]),
+    Color([u8; 4]),
+    Vec(Vec<XnbValue>),
+    Map(Vec<(XnbValue, XnbValue)>),
+    /// A type read by the ReflectiveReader, fields in declaration order.
+    Object { type_name: String, fields: Vec<XnbValue> },
+    Texture(Vec<Sprite>),
+    Sound(Sound),
+    Font(Font),
+    Effect(Effect),
+    /// Index into the shared resources, only seen while the file is being read.
+    SharedResource(usize),
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct Rectangle {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
 #[derive(Debug, Error)]
 pub enum XNBError {
     #[error("Not an XNB file")]
     NotAnXNBFile,
-    #[error("Shared Resources are not supported.")]
-    SharedResourcesNotSupported,
+    #[error("Unknown target platform '{0}'.")]
+    UnknownPlatform(char),
+    #[error("XNB format version {0} is not supported, only XNA 3.1 (4) and XNA 4.0 (5) are.")]
+    UnsupportedVersion(u8),
+    #[error("{platform:?} content can't be built as {version:?}.")]
+    UnsupportedPlatform { platform: Platform, version: FormatVersion },
+    #[error("Unknown XNB flags {0:#04x}.")]
+    UnknownFlags(u8),
+    #[error("File size {0} is smaller than the XNB header.")]
+    InvalidFileSize(i32),
     #[error("Primary asset not found.")]
     PrimaryAssetNotFound,
+    #[error("Type reader index {0} is out of range.")]
+    InvalidTypeReaderIndex(i32),
+    #[error("No type reader registered for {0}.")]
+    UnknownTypeReader(String),
+    #[error("Shared resource {0} does not exist.")]
+    InvalidSharedResource(usize),
+    #[error("Expected a {0}.")]
+    UnexpectedObject(&'static str),
+    #[error("Needed {needed} more bytes at offset {offset}, but the data ended.")]
+    Truncated { offset: usize, needed: usize },
+    #[error("Invalid 7-bit encoded integer at offset {offset}.")]
+    InvalidVarint { offset: usize },
+    #[error("Invalid UTF-8 string at offset {offset}.")]
+    InvalidString { offset: usize },
+    #[error("LZX decompression failed at offset {offset}: {message}")]
+    Lzx { offset: usize, message: String },
+    #[error("Unexpected surface format {format} at offset {offset}.")]
+    UnexpectedSurfaceFormat { offset: usize, format: u32 },
+    #[error("Objects nested too deeply at offset {offset}.")]
+    TooDeeplyNested { offset: usize },
+    #[error("Expected {expected} bytes of texture data at offset {offset}, got {actual}.")]
+    SizeMismatch { offset: usize, expected: usize, actual: usize },
+    #[error("Invalid effect bytecode at offset {offset}.")]
+    InvalidEffect { offset: usize },
 
+    #[error(transparent)]
+    Lz4Error(#[from] lz4_flex::block::DecompressError),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
 
-pub fn convert_xnb_file(file_in: PathBuf) -> Result<XNBFile, XNBError> {
-    let mut file = File::open(file_in)?;
-    let mut vec = Vec::new();
-    file.read_to_end(&mut vec)?;
-    let mut data = Bytes::from(vec);
+impl From<Truncated> for XNBError {
+    fn from(Truncated { offset, needed }: Truncated) -> XNBError {
+        XNBError::Truncated { offset, needed }
+    }
+}
+
+pub fn convert_xnb_file(file_in: PathBuf, registry: &TypeReaderRegistry) -> Result<XNBFile, XNBError> {
+    read_xnb(open_file(file_in)?, registry)
+}
+
+/// Reads an XNB file from any source. The content is decompressed while it is read,
+/// so large assets never have to be held in memory more than once.
+pub fn read_xnb(mut data: XnbInput, registry: &TypeReaderRegistry) -> Result<XNBFile, XNBError> {
+    let header = read_header(&mut data)?;
+    read_xnb_data(content(data, &header)?, &header, registry)
+}
+
+/// Reads the header, type readers and texture description of an XNB file without decoding its content.
+/// LZX frames are only decompressed as far as they are read, LZ4 blocks can only be decompressed as a whole.
+pub fn inspect_xnb(file_in: PathBuf) -> Result<XnbHeader, XNBError> {
+    let mut data = open_file(file_in)?;
+    let mut header = read_header(&mut data)?;
+    let input = content(data, &header)?;
+    read_contents(&mut header, input)?;
+    Ok(header)
+}
+
+fn open_file(file_in: PathBuf) -> Result<XnbInput<'static>, XNBError> {
+    let file = File::open(file_in)?;
+    let length = file.metadata()?.len() as usize;
+    Ok(XnbInput::new(BufReader::new(file), length))
+}
 
-    if data.split_to(3).as_ref() != b"XNB" {
+// Everything after the header, decompressed if needed.
+fn content<'a>(mut data: XnbInput<'a>, header: &XnbHeader) -> Result<XnbInput<'a>, XNBError> {
+    // Compressed data takes up the rest of the file after the header.
+    let input_length = || header.file_size.checked_sub(HEADER_SIZE)
+        .and_then(|length| usize::try_from(length).ok())
+        .ok_or(XNBError::InvalidFileSize(header.file_size));
+    let output_length = usize::try_from(header.decompressed_size).unwrap_or(0);
+
+    Ok(match header.compression {
+        Some(Compression::Lzx) => XnbInput::new(LzxReader::new(data, input_length()?), output_length),
+        Some(Compression::Lz4) => {
+            // MonoGame writes a single LZ4 block without any framing.
+            let input = data.read_bytes(input_length()?)?;
+            let vec = lz4_flex::block::decompress(&input, output_length)?;
+            let length = vec.len();
+            XnbInput::new(Cursor::new(vec), length)
+        }
+        None => data,
+    })
+}
+
+fn read_header(data: &mut XnbInput) -> Result<XnbHeader, XNBError> {
+    if data.remaining() < 3 || data.read_bytes(3)? != b"XNB" {
         return Err(XNBError::NotAnXNBFile);
     }
 
-    let _platform = data.get_u8();
-    let _version = data.get_u8();
+    let platform = data.get_u8()?;
+    let platform = Platform::from_id(platform).ok_or(XNBError::UnknownPlatform(platform as char))?;
+    let version = data.get_u8()?;
+    let version = FormatVersion::from_id(version).ok_or(XNBError::UnsupportedVersion(version))?;
 
-    let compressed = (data.get_u8() & 0x80) != 0;
+    // Only XNA 3.1's own platforms, and neither profiles nor LZ4 compression existed yet.
+    let xna31_platform = matches!(platform, Platform::Windows | Platform::WindowsPhone | Platform::Xbox360);
+    if version == FormatVersion::Xna31 && !xna31_platform {
+        return Err(XNBError::UnsupportedPlatform { platform, version });
+    }
+    let known_flags = match version {
+        FormatVersion::Xna31 => FLAG_LZX,
+        FormatVersion::Xna40 => FLAG_HIDEF | FLAG_LZX | FLAG_LZ4,
+    };
 
-    let compressed_size = data.get_i32_le();
-    let decompressed_size = if compressed {
-        data.get_i32_le()
-    } else {
-        compressed_size
+    let flags = data.get_u8()?;
+    if flags & !known_flags != 0 {
+        return Err(XNBError::UnknownFlags(flags));
+    }
+    let compression = match flags & !FLAG_HIDEF {
+        0 => None,
+        FLAG_LZX => Some(Compression::Lzx),
+        FLAG_LZ4 => Some(Compression::Lz4),
+        _ => return Err(XNBError::UnknownFlags(flags)),
     };
 
-    if compressed {
-        let vec = decompress_lzx(
-            &mut data,
-            (compressed_size - HEADER_SIZE) as u32,
-            decompressed_size as u32,
-        );
-        read_xnb_data(&mut Bytes::from(vec))
+    let file_size = data.get_i32_le()?;
+    let decompressed_size = if compression.is_some() {
+        data.get_i32_le()?
     } else {
-        read_xnb_data(&mut data)
+        file_size.checked_sub(UNCOMPRESSED_HEADER_SIZE).ok_or(XNBError::InvalidFileSize(file_size))?
+    };
+
+    Ok(XnbHeader {
+        platform,
+        version,
+        hidef: flags & FLAG_HIDEF != 0,
+        compression,
+        file_size,
+        decompressed_size,
+        type_readers: Vec::new(),
+        shared_resources: 0,
+        texture: None,
+    })
+}
+
+// The type reader list, and the surface header when the primary asset is a texture.
+fn read_contents(header: &mut XnbHeader, mut input: XnbInput) -> Result<(), XNBError> {
+    header.type_readers.clear();
+    let type_reader_count = input.get_varint()?;
+    for _ in 0..type_reader_count {
+        let name = input.get_string()?;
+        let version = input.get_i32_le()?;
+        header.type_readers.push(TypeReaderInfo { name, version });
+    }
+
+    header.shared_resources = input.get_varint()?;
+
+    let index = input.get_varint()?;
+    let primary = index.checked_sub(1)
+        .and_then(|index| usize::try_from(index).ok())
+        .and_then(|index| header.type_readers.get(index));
+    if primary.is_some_and(|reader| normalize_reader_name(&reader.name) == normalize_reader_name(TEXTURE_2D_READER)) {
+        let format = input.get_u32_le()?;
+        header.texture = Some(TextureInfo {
+            format: SurfaceFormat::read(format, header.version).map_or_else(|| format!("Unknown({})", format), |format| format!("{:?}", format)),
+            width: input.get_u32_le()?,
+            height: input.get_u32_le()?,
+            mip_levels: input.get_u32_le()?,
+        });
+    }
+
+    Ok(())
+}
+
+/// Writes an uncompressed XNA 4.0 Texture2D with a single Color surface.
+pub fn write_xnb_texture(file_out: PathBuf, sprite: &Sprite, alpha: AlphaMode) -> Result<(), XNBError> {
+    let mut sprite = sprite.clone();
+    if alpha == AlphaMode::Straight {
+        surface::premultiply(&mut sprite);
+    }
+
+    let mut data = Vec::new();
+    // Type readers
+    put_varint(&mut data, 1);
+    put_string(&mut data, TEXTURE_2D_READER);
+    data.put_i32_le(0);
+    // Shared resources
+    put_varint(&mut data, 0);
+
+    // Primary asset, 1 being the index of the Texture2DReader
+    put_varint(&mut data, 1);
+    data.put_u32_le(0); // SurfaceFormat.Color
+    data.put_u32_le(sprite.width());
+    data.put_u32_le(sprite.height());
+    data.put_u32_le(1); // mip levels
+    data.put_u32_le(sprite.as_raw().len() as u32);
+    data.put_slice(sprite.as_raw());
+
+    let mut file = File::create(file_out)?;
+    file.write_all(b"XNB")?;
+    // Windows, XNA 4.0, Reach profile and uncompressed
+    file.write_all(&[b'w', 5, 0])?;
+    // The file size includes the 10 byte header.
+    file.write_all(&((data.len() + UNCOMPRESSED_HEADER_SIZE as usize) as u32).to_le_bytes())?;
+    file.write_all(&data)?;
+    Ok(())
+}
+
+fn read_xnb_data(input: XnbInput, header: &XnbHeader, registry: &TypeReaderRegistry) -> Result<XNBFile, XNBError> {
+    let mut reader = ContentReader {
+        input,
+        platform: header.platform,
+        version: header.version,
+        type_readers: Vec::new(),
+        registry,
+        depth: 0,
+    };
+    let input = &mut reader.input;
+
+    let type_reader_count = input.get_varint()?;
+    for _ in 0..type_reader_count {
+        let type_reader_name = input.get_string()?;
+        // reader / version
+        let _reader_version = input.get_i32_le()?;
+
+        let (name, arguments) = parse_type_name(&type_reader_name);
+        reader.type_readers.push((name.to_owned(), arguments));
+    }
+
+    let shared_resource_count = input.get_varint()?;
+
+    let primary = match reader.read_object() {
+        Ok(XnbValue::Null) => return Err(XNBError::PrimaryAssetNotFound),
+        // Without knowing how large the unknown object is nothing after it can be read.
+        Err(XNBError::UnknownTypeReader(_)) => return Ok(XNBFile::Unknown),
+        primary => primary?,
+    };
+
+    let mut shared = Vec::new();
+    for _ in 0..shared_resource_count {
+        shared.push(reader.read_object()?);
     }
+
+    Ok(match resolve_shared_resources(primary, &shared, 0)? {
+        XnbValue::Texture(levels) => XNBFile::Texture(levels),
+        XnbValue::Sound(sound) => XNBFile::Sound(sound),
+        XnbValue::Font(font) => XNBFile::Font(font),
+        XnbValue::Effect(effect) => XNBFile::Effect(effect),
+        value => XNBFile::Value(value),
+    })
 }
 
-fn read_xnb_data(input: &mut Bytes) -> Result<XNBFile, XNBError> {
-    let type_reader_count = get_varint(input);
-    let mut type_reader_name = xnb_get_string(input);
+pub struct ContentReader<'a> {
+    pub input: XnbInput<'a>,
+    platform: Platform,
+    version: FormatVersion,
+    // Type reader names with their generic arguments.
+    type_readers: Vec<(String, Vec<String>)>,
+    registry: &'a TypeReaderRegistry,
+    // How many objects are being read, nested inside each other.
+    depth: usize,
+}
 
-    // reader / version
-    let _reader_version = input.get_i32();
+impl<'a> ContentReader<'a> {
+    pub fn registry(&self) -> &'a TypeReaderRegistry {
+        self.registry
+    }
 
-    if let Some(asem_info_index) = type_reader_name.find(',') {
-        type_reader_name.truncate(asem_info_index);
+    pub fn platform(&self) -> Platform {
+        self.platform
     }
 
-    for _ in 1..type_reader_count {
-        xnb_get_string(input);
-        input.get_i32();
+    pub fn version(&self) -> FormatVersion {
+        self.version
     }
 
-    let resources = get_varint(input);
-    if resources != 0 {
-        return Err(XNBError::SharedResourcesNotSupported);
+    /// Reads an object prefixed by the index of its type reader, 0 being null.
+    pub fn read_object(&mut self) -> Result<XnbValue, XNBError> {
+        let index = self.input.get_varint()?;
+        if index == 0 {
+            return Ok(XnbValue::Null);
+        }
+
+        let (name, arguments) = index.checked_sub(1)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| self.type_readers.get(index))
+            .ok_or(XNBError::InvalidTypeReaderIndex(index))?
+            .clone();
+        let type_reader = self.registry.get(&name).ok_or(XNBError::UnknownTypeReader(name))?;
+        if self.depth >= MAX_DEPTH {
+            return Err(XNBError::TooDeeplyNested { offset: self.input.offset() });
+        }
+
+        self.depth += 1;
+        let value = type_reader.read(self, &arguments);
+        self.depth -= 1;
+        value
+    }
+
+    /// Reads an object of the given type. Value types are stored inline,
+    /// everything else is prefixed by its type reader like in read_object.
+    pub fn read_value(&mut self, type_name: &str) -> Result<XnbValue, XNBError> {
+        match self.registry.get_value_type(type_name) {
+            Some(type_reader) => type_reader.read(self, &parse_type_name(type_name).1),
+            None => self.read_object(),
+        }
     }
 
-    let assets = get_varint(input);
-    if assets != 1 {
-        return Err(XNBError::PrimaryAssetNotFound);
+    /// Reads a reference to one of the shared resources stored after the primary asset.
+    /// It is replaced by the resource itself once every shared resource has been read.
+    pub fn read_shared_resource(&mut self) -> Result<XnbValue, XNBError> {
+        Ok(match self.input.get_varint()? {
+            0 => XnbValue::Null,
+            index => XnbValue::SharedResource(index as usize - 1),
+        })
     }
+}
 
-    Ok(match type_reader_name.as_str() {
-        "Microsoft.Xna.Framework.Content.Texture2DReader" => {
-            let _surface_format = input.get_u32_le();
-            let width = input.get_u32_le();
-            let height = input.get_u32_le();
-            let _mipmaps = input.get_u32_le();
-            let _size = input.get_u32_le();
+fn resolve_shared_resources(value: XnbValue, shared: &[XnbValue], depth: usize) -> Result<XnbValue, XNBError> {
+    let resolve_all = |values: Vec<XnbValue>| {
+        values.into_iter()
+            .map(|value| resolve_shared_resources(value, shared, depth))
+            .collect::<Result<Vec<_>, _>>()
+    };
 
-            let texture = RgbaImage::from_raw(width, height, input.to_vec()).unwrap();
-            XNBFile::Texture(texture)
+    Ok(match value {
+        // Shared resources may point at each other, but never in a cycle.
+        XnbValue::SharedResource(index) if depth <= shared.len() => {
+            let resource = shared.get(index).ok_or(XNBError::InvalidSharedResource(index))?;
+            resolve_shared_resources(resource.clone(), shared, depth + 1)?
         }
-        _ => XNBFile::Unknown,
+        XnbValue::SharedResource(index) => return Err(XNBError::InvalidSharedResource(index)),
+        XnbValue::Vec(values) => XnbValue::Vec(resolve_all(values)?),
+        XnbValue::Map(entries) => XnbValue::Map(entries.into_iter()
+            .map(|(key, value)| Ok((resolve_shared_resources(key, shared, depth)?, resolve_shared_resources(value, shared, depth)?)))
+            .collect::<Result<_, XNBError>>()?),
+        XnbValue::Object { type_name, fields } => XnbValue::Object { type_name, fields: resolve_all(fields)? },
+        value => value,
     })
 }
 
-//ByteBuffer input, int inputLength, ByteBuffer output, int outputLength
-fn decompress_lzx(input: &mut Bytes, input_length: u32, output_length: u32) -> Vec<u8> {
-    let mut remaining = input_length;
-    let mut out = Vec::with_capacity(output_length as usize);
-    let mut chunk_decompressor = Lzxd::new(WindowSize::KB64);
+impl Serialize for XnbValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            XnbValue::Null => serializer.serialize_unit(),
+            XnbValue::Bool(value) => serializer.serialize_bool(*value),
+            XnbValue::Int(value) => serializer.serialize_i64(*value),
+            XnbValue::UInt(value) => serializer.serialize_u64(*value),
+            XnbValue::Float(value) => serializer.serialize_f64(*value),
+            XnbValue::Char(value) => serializer.serialize_char(*value),
+            XnbValue::String(value) => serializer.serialize_str(value),
+            XnbValue::Vector(value) => value.serialize(serializer),
+            XnbValue::Rect(value) => value.serialize(serializer),
+            XnbValue::Point(value) => value.serialize(serializer),
+            XnbValue::Color(value) => value.serialize(serializer),
+            XnbValue::Vec(values) => values.serialize(serializer),
+            XnbValue::Map(entries) => serializer.collect_map(entries.iter().map(|(key, value)| (key, value))),
+            XnbValue::Object { type_name, fields } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", type_name)?;
+                map.serialize_entry("fields", fields)?;
+                map.end()
+            }
+            XnbValue::Texture(levels) => {
+                let mut map = serializer.serialize_map(Some(3))?;
+                map.serialize_entry("width", &levels.first().map_or(0, |sprite| sprite.width()))?;
+                map.serialize_entry("height", &levels.first().map_or(0, |sprite| sprite.height()))?;
+                map.serialize_entry("mipmaps", &levels.len())?;
+                map.end()
+            }
+            XnbValue::Sound(sound) => {
+                let mut map = serializer.serialize_map(Some(4))?;
+                map.serialize_entry("channels", &sound.format.channels)?;
+                map.serialize_entry("sample_rate", &sound.format.sample_rate)?;
+                map.serialize_entry("loop_start", &sound.loop_start)?;
+                map.serialize_entry("loop_length", &sound.loop_length)?;
+                map.end()
+            }
+            XnbValue::Font(font) => font.metrics().serialize(serializer),
+            XnbValue::Effect(effect) => effect.describe().map_err(serde::ser::Error::custom)?.serialize(serializer),
+            XnbValue::SharedResource(index) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("shared_resource", index)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// Decompresses the LZX frames following the header as they are read.
+struct LzxReader<'a> {
+    input: XnbInput<'a>,
+    // Compressed bytes left, including the frame headers.
+    remaining: usize,
+    decompressor: Lzxd,
+    chunk: Vec<u8>,
+    frame: Vec<u8>,
+    position: usize,
+}
 
-    while remaining > 0 {
-        let mut hi = input.get_u8();
-        let mut lo = input.get_u8();
-        let mut block_size: u32 = ((hi as u32) << 8u32) | lo as u32;
-        let mut frame_size: u32 = 0x8000;
+impl<'a> LzxReader<'a> {
+    fn new(input: XnbInput<'a>, input_length: usize) -> LzxReader<'a> {
+        LzxReader {
+            input,
+            remaining: input_length,
+            decompressor: Lzxd::new(WindowSize::KB64),
+            chunk: Vec::new(),
+            frame: Vec::new(),
+            position: 0,
+        }
+    }
+
+    // Decompresses the next frame, false once there are none left.
+    fn next_frame(&mut self) -> Result<bool, XNBError> {
+        if self.remaining == 0 {
+            return Ok(false);
+        }
+
+        let input = &mut self.input;
+        let offset = input.offset();
+        let mut hi = input.get_u8()?;
+        let mut lo = input.get_u8()?;
+        let mut block_size = ((hi as usize) << 8) | lo as usize;
+        let mut frame_size = LZX_FRAME_SIZE;
+        let mut header_size = 2;
 
         if hi == 0xFF {
             hi = lo;
-            lo = input.get_u8();
-            frame_size = ((hi as u32) << 8u32) | lo as u32;
-            hi = input.get_u8();
-            lo = input.get_u8();
-            block_size = ((hi as u32) << 8u32) | lo as u32;
-            remaining -= 5;
-        } else {
-            remaining -= 2;
+            lo = input.get_u8()?;
+            frame_size = ((hi as usize) << 8) | lo as usize;
+            hi = input.get_u8()?;
+            lo = input.get_u8()?;
+            block_size = ((hi as usize) << 8) | lo as usize;
+            header_size = 5;
         }
 
         if block_size == 0 || frame_size == 0 {
-            return out;
+            self.remaining = 0;
+            return Ok(false);
         }
 
-        let chunk = &input[0..block_size as usize];
-        let result = chunk_decompressor.decompress_next(chunk);
-        input.advance(block_size as usize);
-        if let Ok(data) = result {
-            out.write_all(data).unwrap();
-        } else {
-            panic!("Failed {}", result.unwrap_err());
+        self.chunk.resize(block_size, 0);
+        input.read_exact(&mut self.chunk)?;
+        let data = self.decompressor
+            .decompress_next(&self.chunk)
+            .map_err(|error| XNBError::Lzx { offset, message: error.to_string() })?;
+
+        self.frame.clear();
+        self.frame.extend_from_slice(data);
+        self.position = 0;
+        self.remaining = self.remaining.saturating_sub(header_size + block_size);
+        Ok(true)
+    }
+}
+
+impl Read for LzxReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.frame.len() {
+            // XnbInput unwraps the error again.
+            if !self.next_frame().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))? {
+                return Ok(0);
+            }
         }
 
-        remaining -= block_size as u32;
+        let count = buf.len().min(self.frame.len() - self.position);
+        buf[..count].copy_from_slice(&self.frame[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+fn put_varint(output: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        output.put_u8((value as u8) | 0x80);
+        value >>= 7;
     }
-    out
+    output.put_u8(value as u8);
 }
 
-fn get_varint(input: &mut Bytes) -> i32 {
-    let mut result: i32 = 0;
-    let mut bits_read = 0;
-    loop {
-        let value = input.get_u8();
-        result |= (value as i32 & 0x7f) << bits_read;
-        bits_read += 7;
+fn put_string(output: &mut Vec<u8>, value: &str) {
+    put_varint(output, value.len() as u32);
+    output.put_slice(value.as_bytes());
+}
 
-        if (value & 0x80) == 0 {
-            break;
+/// Bounds checked reading of XNB data. Offsets count from the start of the
+/// decompressed content, or from the start of the file while reading the header.
+pub struct XnbInput<'a> {
+    source: Box<dyn Read + 'a>,
+    offset: usize,
+    // Where the data ends, even if the source has more.
+    length: usize,
+}
+
+macro_rules! get {
+    ($($NAME:ident -> $TYPE:ty;)*) => {$(
+        pub fn $NAME(&mut self) -> Result<$TYPE, XNBError> {
+            let mut bytes = [0; std::mem::size_of::<$TYPE>()];
+            self.read_exact(&mut bytes)?;
+            Ok(<$TYPE>::from_le_bytes(bytes))
         }
+    )*};
+}
+
+impl<'a> XnbInput<'a> {
+    pub fn new(source: impl Read + 'a, length: usize) -> XnbInput<'a> {
+        XnbInput { source: Box::new(source), offset: 0, length }
     }
 
-    result
-}
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-fn xnb_get_string(input: &mut Bytes) -> String {
-    let length = get_varint(input);
-    let bytes = input.split_to(length as usize);
-    String::from_utf8(bytes.to_vec()).unwrap()
+    pub fn remaining(&self) -> usize {
+        self.length.saturating_sub(self.offset)
+    }
+
+    fn ensure(&self, needed: usize) -> Result<(), XNBError> {
+        if self.remaining() < needed {
+            return Err(XNBError::Truncated { offset: self.offset, needed });
+        }
+        Ok(())
+    }
+
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), XNBError> {
+        self.ensure(buf.len())?;
+        if let Err(error) = self.source.read_exact(buf) {
+            return Err(self.read_error(error, buf.len()));
+        }
+        self.offset += buf.len();
+        Ok(())
+    }
+
+    /// Reads the next `count` bytes into a buffer of exactly that size.
+    pub fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, XNBError> {
+        self.ensure(count)?;
+        let mut bytes = vec![0; count];
+        self.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // Errors of the LZX reader are passed through io::Error.
+    fn read_error(&self, error: io::Error, needed: usize) -> XNBError {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            return XNBError::Truncated { offset: self.offset, needed };
+        }
+        if !error.get_ref().is_some_and(|inner| inner.is::<XNBError>()) {
+            return error.into();
+        }
+
+        match error.into_inner().map(|inner| inner.downcast::<XNBError>()) {
+            Some(Ok(error)) => *error,
+            _ => XNBError::Truncated { offset: self.offset, needed },
+        }
+    }
+
+    get! {
+        get_u8 -> u8;
+        get_i8 -> i8;
+        get_u16_le -> u16;
+        get_i16_le -> i16;
+        get_u32_le -> u32;
+        get_i32_le -> i32;
+        get_u64_le -> u64;
+        get_i64_le -> i64;
+        get_f32_le -> f32;
+        get_f64_le -> f64;
+    }
+
+    pub fn get_varint(&mut self) -> Result<i32, XNBError> {
+        let offset = self.offset();
+        let mut result: i32 = 0;
+        let mut bits_read = 0;
+        loop {
+            // An i32 never takes more than 5 bytes.
+            if bits_read > 28 {
+                return Err(XNBError::InvalidVarint { offset });
+            }
+
+            let value = self.get_u8()?;
+            result |= (value as i32 & 0x7f) << bits_read;
+            bits_read += 7;
+
+            if (value & 0x80) == 0 {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn get_string(&mut self) -> Result<String, XNBError> {
+        let length = self.get_varint()?;
+        let offset = self.offset();
+        let length = usize::try_from(length).map_err(|_| XNBError::InvalidString { offset })?;
+        String::from_utf8(self.read_bytes(length)?).map_err(|_| XNBError::InvalidString { offset })
+    }
+
+    // .NET BinaryReader.ReadChar, a single UTF-8 encoded character.
+    pub fn get_char(&mut self) -> Result<char, XNBError> {
+        let offset = self.offset();
+        let first = self.get_u8()?;
+        let length = match first {
+            0x00..=0x7F => return Ok(first as char),
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+
+        let mut bytes = [first, 0, 0, 0];
+        self.read_exact(&mut bytes[1..length])?;
+        std::str::from_utf8(&bytes[..length])
+            .ok()
+            .and_then(|string| string.chars().next())
+            .ok_or(XNBError::InvalidString { offset })
+    }
 }
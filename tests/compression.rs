@@ -0,0 +1,121 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use oxidizer::asset::Sprite;
+use oxidizer::reader::TypeReaderRegistry;
+use oxidizer::surface::AlphaMode;
+use oxidizer::xnb::{self, XNBError, XNBFile, XnbInput};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn read(name: &str) -> Result<XNBFile, XNBError> {
+    xnb::convert_xnb_file(fixture(name), &TypeReaderRegistry::builtin(AlphaMode::Premultiplied))
+}
+
+// Reads a fixture after letting `corrupt` change its bytes.
+fn read_corrupted(name: &str, corrupt: impl FnOnce(&mut Vec<u8>)) -> Result<XNBFile, XNBError> {
+    let mut data = std::fs::read(fixture(name)).unwrap();
+    corrupt(&mut data);
+    let length = data.len();
+    xnb::read_xnb(XnbInput::new(Cursor::new(data), length), &TypeReaderRegistry::builtin(AlphaMode::Premultiplied))
+}
+
+// The uncompressed 4x4 Color texture. The type reader name ends at 110, followed by its version,
+// the shared resource count, the primary asset's type reader index at 115 and the texture header at 116.
+fn read_plain(corrupt: impl FnOnce(&mut Vec<u8>)) -> Result<XNBFile, XNBError> {
+    read_corrupted("texture.xnb", corrupt)
+}
+
+fn texture(name: &str) -> Sprite {
+    match read(name) {
+        Ok(XNBFile::Texture(mut levels)) => levels.swap_remove(0),
+        Ok(_) => panic!("{} is not a texture", name),
+        Err(error) => panic!("{}: {}", name, error),
+    }
+}
+
+#[test]
+fn lzx_and_lz4_decode_to_the_same_pixels() {
+    let lzx = texture("texture_lzx.xnb");
+    let lz4 = texture("texture_lz4.xnb");
+
+    assert_eq!(lzx.dimensions(), (4, 4));
+    assert_eq!(lzx.get_pixel(1, 0).0, [16, 239, 37, 255]);
+    assert_eq!(lzx.as_raw(), lz4.as_raw());
+}
+
+#[test]
+fn unknown_flags_are_rejected() {
+    assert!(matches!(read("unknown_flags.xnb"), Err(XNBError::UnknownFlags(0x20))));
+}
+
+#[test]
+fn corrupt_lz4_is_an_error() {
+    // The decompressed size is smaller than the content of the LZ4 block.
+    assert!(matches!(read("corrupt_lz4.xnb"), Err(XNBError::Lz4Error(_))));
+}
+
+#[test]
+fn plain_fixture_is_valid() {
+    assert!(matches!(read_plain(|_| {}), Ok(XNBFile::Texture(_))));
+}
+
+#[test]
+fn truncated_content_is_an_error() {
+    let result = read_plain(|data| data.truncate(150));
+    assert!(matches!(result, Err(XNBError::Truncated { offset: 136, needed: 64 })), "{:?}", result.err());
+}
+
+#[test]
+fn invalid_varint_is_an_error() {
+    let result = read_plain(|data| data[10..16].fill(0xFF));
+    assert!(matches!(result, Err(XNBError::InvalidVarint { offset: 10 })), "{:?}", result.err());
+}
+
+#[test]
+fn invalid_string_is_an_error() {
+    let result = read_plain(|data| data[12] = 0xFF);
+    assert!(matches!(result, Err(XNBError::InvalidString { offset: 12 })), "{:?}", result.err());
+}
+
+#[test]
+fn invalid_type_reader_index_is_an_error() {
+    let result = read_plain(|data| data[115] = 5);
+    assert!(matches!(result, Err(XNBError::InvalidTypeReaderIndex(5))), "{:?}", result.err());
+}
+
+#[test]
+fn texture_size_mismatch_is_an_error() {
+    let result = read_plain(|data| data[132] = 63);
+    assert!(matches!(result, Err(XNBError::SizeMismatch { expected: 64, actual: 63, .. })), "{:?}", result.err());
+}
+
+#[test]
+fn huge_texture_is_an_error() {
+    // A width and height of 0xFFFFFFFF overflowed the size of the level.
+    let result = read_plain(|data| data[120..128].fill(0xFF));
+    assert!(matches!(result, Err(XNBError::UnexpectedSurfaceFormat { format: 0, .. })), "{:?}", result.err());
+}
+
+#[test]
+fn file_size_below_header_is_an_error() {
+    let result = read_corrupted("texture_lz4.xnb", |data| data[6..10].copy_from_slice(&5i32.to_le_bytes()));
+    assert!(matches!(result, Err(XNBError::InvalidFileSize(5))), "{:?}", result.err());
+}
+
+#[test]
+fn corrupt_lzx_is_an_error() {
+    // The first frame starts at 19, a block type of 0 does not exist.
+    let result = read_corrupted("texture_lzx.xnb", |data| data[19..21].fill(0));
+    assert!(matches!(result, Err(XNBError::Lzx { .. })), "{:?}", result.err());
+}
+
+#[test]
+fn decompressed_size_excludes_the_header() {
+    for name in ["texture.xnb", "texture_lzx.xnb", "texture_lz4.xnb"] {
+        let header = xnb::inspect_xnb(fixture(name)).unwrap();
+        assert_eq!(header.decompressed_size, 190, "{}", name);
+    }
+}